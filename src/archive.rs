@@ -0,0 +1,240 @@
+#![allow(dead_code)]
+
+use std::{
+    collections::BTreeSet,
+    fs::{create_dir_all, read_dir, read_to_string, rename, write, File},
+    io::{BufWriter, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::paths;
+
+/// Ein archivierter Post mit allen Kommentaren, gespeichert in
+/// `<archive_dir>/<instanz>/<post id>.json`
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Thread {
+    pub instance: String,
+    pub id: i32,
+    /// Jedes Mal wenn der Post archiviert wird und sich etwas geändert hat, kommt eine Revision dazu.
+    /// Die neuste ist immer die letzte.
+    pub revisions: Vec<Revision>,
+}
+
+/// Der Stand eines Posts und seiner Kommentare zu einem bestimmten Zeitpunkt
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Revision {
+    pub archived: DateTime<Local>,
+    pub post: Post,
+    pub comments: Vec<Comment>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Post {
+    pub id: i32,
+    pub community: String,
+    pub author: String,
+    pub title: String,
+    pub body: Option<String>,
+    pub url: Option<String>,
+    pub score: i64,
+    pub published: DateTime<Utc>,
+    pub nsfw: bool,
+    pub deleted: bool,
+    pub removed: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Comment {
+    pub id: i32,
+    /// Der Kommentar auf den geantwortet wird, `None` wenn direkt auf den Post geantwortet wird
+    pub parent: Option<i32>,
+    pub author: String,
+    pub body: String,
+    pub score: i64,
+    pub published: DateTime<Utc>,
+    pub deleted: bool,
+    pub removed: bool,
+}
+
+impl Thread {
+    /// Die neuste Revision, `None` nur bei einer kaputten Datei ohne Revisionen
+    pub fn latest(&self) -> Option<&Revision> {
+        self.revisions.last()
+    }
+
+    /// Der Pfad unter dem ein Thread gespeichert wird
+    pub fn path(instance: &str, id: i32) -> PathBuf {
+        Path::new(&paths().archive_dir())
+            .join(instance)
+            .join(format!("{}.json", id))
+    }
+
+    /// Lädt einen Thread aus dem Archiv, returnt `Ok(None)` wenn er nicht archiviert ist
+    pub fn load(instance: &str, id: i32) -> Result<Option<Thread>, String> {
+        let path = Thread::path(instance, id);
+        match read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map(Some)
+                .map_err(|err| format!("Fehler beim Parsen von {:?}: {}", path, err)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(format!("Fehler beim Lesen von {:?}: {}", path, err)),
+        }
+    }
+
+    /// Speichert den Thread. Es wird erst in eine temporäre Datei geschrieben, damit bei einem Absturz
+    /// keine halb geschriebenen Threads im Archiv landen.
+    pub fn save(&self) -> Result<(), String> {
+        let path = Thread::path(&self.instance, self.id);
+        let tmp = path.with_extension("json.tmp");
+
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)
+                .map_err(|err| format!("Fehler beim Erstellen von {:?}: {}", dir, err))?;
+        }
+
+        let file = File::create(&tmp)
+            .map_err(|err| format!("Fehler beim Erstellen von {:?}: {}", tmp, err))?;
+        serde_json::to_writer(BufWriter::new(file), self)
+            .map_err(|err| format!("Fehler beim Schreiben von {:?}: {}", tmp, err))?;
+        rename(&tmp, &path).map_err(|err| format!("Fehler beim Bewegen von {:?}: {}", tmp, err))?;
+
+        match self.latest() {
+            Some(revision) => add_community(&revision.post.community),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Revision {
+    /// Die direkten Antworten auf den Kommentar `parent`, oder auf den Post wenn `parent` `None` ist.
+    /// Kommentare deren Elternkommentar nicht archiviert wurde zählen als Antwort auf den Post.
+    pub fn replies(&self, parent: Option<i32>) -> impl Iterator<Item = &Comment> {
+        self.comments
+            .iter()
+            .filter(move |comment| match (parent, comment.parent) {
+                (None, Some(comment_parent)) => {
+                    !self.comments.iter().any(|other| other.id == comment_parent)
+                }
+                _ => comment.parent == parent,
+            })
+    }
+}
+
+/// Beschreibt ob ein Post oder Kommentar vom Autor gelöscht oder von einem Moderator entfernt wurde
+pub fn removal_label(deleted: bool, removed: bool) -> Option<&'static str> {
+    match (deleted, removed) {
+        (_, true) => Some("entfernt"),
+        (true, false) => Some("gelöscht"),
+        (false, false) => None,
+    }
+}
+
+/// Alle Instanzen die im Archiv vorkommen
+pub fn instances() -> Result<Vec<String>, String> {
+    let mut instances: Vec<String> = match read_dir(paths().archive_dir()) {
+        Ok(entries) => entries
+            .map_while(Result::ok)
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect(),
+        Err(err) if err.kind() == ErrorKind::NotFound => Vec::new(),
+        Err(err) => {
+            return Err(format!(
+                "Fehler beim Lesen von {}: {}",
+                paths().archive_dir(),
+                err
+            ))
+        }
+    };
+    instances.sort();
+    Ok(instances)
+}
+
+/// Die IDs aller archivierten Posts einer Instanz, aufsteigend sortiert
+pub fn post_ids(instance: &str) -> Result<Vec<i32>, String> {
+    let dir = Path::new(&paths().archive_dir()).join(instance);
+    let mut ids: Vec<i32> = read_dir(&dir)
+        .map_err(|err| format!("Fehler beim Lesen von {:?}: {}", dir, err))?
+        .map_while(Result::ok)
+        .filter_map(|entry| {
+            entry
+                .file_name()
+                .to_str()?
+                .strip_suffix(".json")?
+                .parse()
+                .ok()
+        })
+        .collect();
+    ids.sort();
+    Ok(ids)
+}
+
+/// Der Index der Communities im Archiv, eine pro Zeile. So muss die Shell-Vervollständigung
+/// nicht jeden Thread parsen.
+fn communities_file() -> PathBuf {
+    Path::new(&paths().archive_dir()).join("communities.txt")
+}
+
+/// Alle Communities die im Archiv vorkommen, sortiert. Archive von vor dem Index werden
+/// einmal komplett gelesen, danach steht der Index in `communities.txt`.
+pub fn communities() -> Result<BTreeSet<String>, String> {
+    let path = communities_file();
+    match read_to_string(&path) {
+        Ok(content) => {
+            return Ok(content
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect())
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => return Err(format!("Fehler beim Lesen von {:?}: {}", path, err)),
+    }
+
+    let communities: BTreeSet<String> = threads()?
+        .filter_map(Result::ok)
+        .filter_map(|thread| Some(thread.latest()?.post.community.clone()))
+        .collect();
+    if !communities.is_empty() {
+        let content: String = communities
+            .iter()
+            .map(|name| format!("{}\n", name))
+            .collect();
+        write(&path, content)
+            .map_err(|err| format!("Fehler beim Schreiben von {:?}: {}", path, err))?;
+    }
+    Ok(communities)
+}
+
+/// Trägt `community` in `communities.txt` ein, wenn sie da noch fehlt. Doppelte Zeilen durch
+/// gleichzeitiges Speichern sind harmlos.
+fn add_community(community: &str) -> Result<(), String> {
+    if communities()?.contains(community) {
+        return Ok(());
+    }
+    let path = communities_file();
+    File::options()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| writeln!(file, "{}", community))
+        .map_err(|err| format!("Fehler beim Schreiben von {:?}: {}", path, err))
+}
+
+/// Geht alle archivierten Threads durch. Es wird immer nur ein Thread gleichzeitig geladen, damit
+/// auch große Archive nicht komplett in den Speicher passen müssen.
+pub fn threads() -> Result<impl Iterator<Item = Result<Thread, String>>, String> {
+    let mut ids = Vec::new();
+    for instance in instances()? {
+        for id in post_ids(&instance)? {
+            ids.push((instance.clone(), id));
+        }
+    }
+
+    Ok(ids
+        .into_iter()
+        .filter_map(|(instance, id)| Thread::load(&instance, id).transpose()))
+}
@@ -0,0 +1,719 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::{Read, Write},
+    net::TcpStream,
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState, Paragraph, Wrap},
+    DefaultTerminal, Frame,
+};
+
+use crate::{
+    archive::{self, removal_label, Revision, Thread},
+    config::paths,
+    helpers::{daemon_running, get},
+    search::{search, SearchQuery, Sort},
+    status::Status,
+};
+
+/// Wie oft der Status des Daemons neu geholt wird
+const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+/// Wie lange auf eine Taste gewartet wird, bevor neu gezeichnet wird
+const TICK: Duration = Duration::from_millis(250);
+/// Wie viele Treffer die Suche maximal anzeigt
+const SEARCH_LIMIT: usize = 500;
+
+/// Die Daten eines Posts die für die Liste gebraucht werden, damit nicht alle Threads gleichzeitig
+/// im Speicher liegen müssen
+struct Summary {
+    instance: String,
+    id: i32,
+    community: String,
+    author: String,
+    title: String,
+    score: i64,
+    published: chrono::DateTime<chrono::Utc>,
+    comments: usize,
+    removal: Option<&'static str>,
+}
+
+/// Ein Eintrag in der Seitenleiste, aus dem die Posts in der Liste kommen
+#[derive(Clone, PartialEq)]
+enum Source {
+    All,
+    Instance(String),
+    Community(String, String),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Focus {
+    Sidebar,
+    Posts,
+    Thread,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum InputKind {
+    Filter,
+    Search,
+}
+
+/// Ein geöffneter Thread. `rows` sind die sichtbaren Kommentare mit ihrer Tiefe.
+struct ThreadView {
+    thread: Thread,
+    collapsed: HashSet<i32>,
+    rows: Vec<(usize, usize)>,
+    state: ListState,
+}
+
+struct App {
+    posts: Vec<Summary>,
+    sources: Vec<Source>,
+    sources_state: ListState,
+    /// Die Indizes der Posts die gerade in der Liste stehen
+    visible: Vec<usize>,
+    posts_state: ListState,
+    sort: Sort,
+    filter: String,
+    /// Die Posts auf die die letzte Suche zutrifft, `None` ohne Suche
+    search: Option<(String, HashSet<(String, i32)>)>,
+    input: Option<(InputKind, String)>,
+    focus: Focus,
+    thread: Option<ThreadView>,
+    status: Option<Status>,
+    status_fetched: Option<Instant>,
+    message: Option<String>,
+}
+
+/// Öffnet den Browser im Terminal, bis er mit `q` beendet wird
+pub fn browse() -> Result<(), String> {
+    let mut app = App::load()?;
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn load() -> Result<App, String> {
+        let mut posts = Vec::new();
+        let mut communities: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let mut skipped = 0;
+
+        // Die Warnungen stehen nach dem Beenden noch im Terminal, die Anzahl zeigt der Browser
+        for thread in archive::threads()? {
+            let thread = match thread {
+                Ok(thread) => thread,
+                Err(err) => {
+                    eprintln!("Warnung: Überspringe Thread: {}", err);
+                    skipped += 1;
+                    continue;
+                }
+            };
+            let Some(revision) = thread.latest() else {
+                continue;
+            };
+            let post = &revision.post;
+
+            let instance = communities.entry(thread.instance.clone()).or_default();
+            if !instance.contains(&post.community) {
+                instance.push(post.community.clone());
+            }
+
+            posts.push(Summary {
+                instance: thread.instance.clone(),
+                id: thread.id,
+                community: post.community.clone(),
+                author: post.author.clone(),
+                title: post.title.clone(),
+                score: post.score,
+                published: post.published,
+                comments: revision.comments.len(),
+                removal: removal_label(post.deleted, post.removed),
+            });
+        }
+
+        let mut sources = vec![Source::All];
+        for (instance, mut names) in communities {
+            names.sort();
+            sources.push(Source::Instance(instance.clone()));
+            sources.extend(
+                names
+                    .into_iter()
+                    .map(|name| Source::Community(instance.clone(), name)),
+            );
+        }
+
+        let mut app = App {
+            posts,
+            sources,
+            sources_state: ListState::default().with_selected(Some(0)),
+            visible: Vec::new(),
+            posts_state: ListState::default(),
+            sort: Sort::New,
+            filter: String::new(),
+            search: None,
+            input: None,
+            focus: Focus::Posts,
+            thread: None,
+            status: None,
+            status_fetched: None,
+            message: (skipped > 0).then(|| format!("{} unlesbare Threads übersprungen", skipped)),
+        };
+        app.refresh_posts();
+        Ok(app)
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), String> {
+        loop {
+            if self
+                .status_fetched
+                .map_or(true, |fetched| fetched.elapsed() >= STATUS_INTERVAL)
+            {
+                self.status = fetch_status();
+                self.status_fetched = Some(Instant::now());
+            }
+
+            terminal
+                .draw(|frame| self.draw(frame))
+                .map_err(|err| format!("Fehler beim Zeichnen: {}", err))?;
+
+            if !event::poll(TICK).map_err(|err| err.to_string())? {
+                continue;
+            }
+            let Event::Key(key) = event::read().map_err(|err| err.to_string())? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            if self.input.is_some() {
+                self.handle_input(key.code);
+            } else if !self.handle_key(key.code) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Returnt `false` wenn der Browser beendet werden soll
+    fn handle_key(&mut self, key: KeyCode) -> bool {
+        self.message = None;
+
+        match (self.focus, key) {
+            (Focus::Thread, KeyCode::Esc | KeyCode::Char('q') | KeyCode::Backspace) => {
+                self.thread = None;
+                self.focus = Focus::Posts;
+            }
+            (_, KeyCode::Char('q')) => return false,
+            (Focus::Thread, KeyCode::Enter | KeyCode::Char(' ')) => self.toggle_comment(),
+            (Focus::Thread, KeyCode::Char('c')) => self.collapse_all(true),
+            (Focus::Thread, KeyCode::Char('e')) => self.collapse_all(false),
+            (Focus::Thread, KeyCode::Up | KeyCode::Char('k')) => {
+                if let Some(view) = &mut self.thread {
+                    view.state.select_previous();
+                }
+            }
+            (Focus::Thread, KeyCode::Down | KeyCode::Char('j')) => {
+                if let Some(view) = &mut self.thread {
+                    view.state.select_next();
+                }
+            }
+            (Focus::Thread, _) => {}
+            (_, KeyCode::Tab) => {
+                self.focus = match self.focus {
+                    Focus::Sidebar => Focus::Posts,
+                    _ => Focus::Sidebar,
+                }
+            }
+            (_, KeyCode::Char('/')) => self.input = Some((InputKind::Search, String::new())),
+            (_, KeyCode::Char('f')) => {
+                self.input = Some((InputKind::Filter, self.filter.clone()));
+            }
+            (_, KeyCode::Char('s')) => {
+                self.sort = match self.sort {
+                    Sort::New => Sort::Old,
+                    Sort::Old => Sort::Score,
+                    Sort::Score => Sort::New,
+                };
+                self.refresh_posts();
+            }
+            (_, KeyCode::Esc) => {
+                self.search = None;
+                self.filter.clear();
+                self.refresh_posts();
+            }
+            (Focus::Sidebar, KeyCode::Up | KeyCode::Char('k')) => {
+                self.sources_state.select_previous();
+                self.refresh_posts();
+            }
+            (Focus::Sidebar, KeyCode::Down | KeyCode::Char('j')) => {
+                self.sources_state.select_next();
+                self.refresh_posts();
+            }
+            (Focus::Sidebar, KeyCode::Enter) => self.focus = Focus::Posts,
+            (Focus::Posts, KeyCode::Up | KeyCode::Char('k')) => self.posts_state.select_previous(),
+            (Focus::Posts, KeyCode::Down | KeyCode::Char('j')) => self.posts_state.select_next(),
+            (Focus::Posts, KeyCode::Enter) => self.open_thread(),
+            _ => {}
+        }
+
+        true
+    }
+
+    fn handle_input(&mut self, key: KeyCode) {
+        let Some((kind, text)) = &mut self.input else {
+            return;
+        };
+
+        match key {
+            KeyCode::Esc => self.input = None,
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Char(c) => text.push(c),
+            KeyCode::Enter => {
+                let (kind, text) = (*kind, text.clone());
+                self.input = None;
+                match kind {
+                    InputKind::Filter => self.filter = text,
+                    InputKind::Search if text.trim().is_empty() => self.search = None,
+                    InputKind::Search => self.run_search(text),
+                }
+                self.refresh_posts();
+            }
+            _ => {}
+        }
+    }
+
+    fn run_search(&mut self, text: String) {
+        let query = SearchQuery {
+            text: text.clone(),
+            community: None,
+            author: None,
+            instance: None,
+            since: None,
+            until: None,
+            min_score: None,
+            sort: self.sort,
+            limit: Some(SEARCH_LIMIT),
+        };
+
+        // Im TUI kann nicht einfach geprintet werden, übersprungene Threads werden nur gezählt
+        let mut skipped = 0;
+        match search(&query, |_| skipped += 1) {
+            Ok(hits) => {
+                let mut message = format!("{} Treffer für \"{}\"", hits.len(), text);
+                if skipped > 0 {
+                    message.push_str(&format!(" ({} unlesbare Threads übersprungen)", skipped));
+                }
+                self.message = Some(message);
+                let posts = hits
+                    .into_iter()
+                    .map(|hit| (hit.instance, hit.post_id))
+                    .collect();
+                self.search = Some((text, posts));
+            }
+            Err(err) => self.message = Some(format!("Fehler bei der Suche: {}", err)),
+        }
+    }
+
+    /// Berechnet die sichtbaren Posts neu, nachdem sich Quelle, Filter, Suche oder Sortierung
+    /// geändert haben
+    fn refresh_posts(&mut self) {
+        let source = self
+            .sources_state
+            .selected()
+            .and_then(|index| self.sources.get(index))
+            .cloned()
+            .unwrap_or(Source::All);
+        let filter = self.filter.to_lowercase();
+
+        self.visible = (0..self.posts.len())
+            .filter(|index| {
+                let post = &self.posts[*index];
+                let in_source = match &source {
+                    Source::All => true,
+                    Source::Instance(instance) => post.instance == *instance,
+                    Source::Community(instance, community) => {
+                        post.instance == *instance && post.community == *community
+                    }
+                };
+                let in_search = self.search.as_ref().map_or(true, |(_, posts)| {
+                    posts.contains(&(post.instance.clone(), post.id))
+                });
+                let in_filter = filter.is_empty()
+                    || [&post.title, &post.author, &post.community]
+                        .iter()
+                        .any(|field| field.to_lowercase().contains(&filter));
+                in_source && in_search && in_filter
+            })
+            .collect();
+
+        let posts = &self.posts;
+        match self.sort {
+            Sort::New => self
+                .visible
+                .sort_by_key(|index| std::cmp::Reverse(posts[*index].published)),
+            Sort::Old => self.visible.sort_by_key(|index| posts[*index].published),
+            Sort::Score => self
+                .visible
+                .sort_by_key(|index| std::cmp::Reverse(posts[*index].score)),
+        }
+
+        self.posts_state
+            .select((!self.visible.is_empty()).then_some(0));
+    }
+
+    fn open_thread(&mut self) {
+        let Some(index) = self
+            .posts_state
+            .selected()
+            .and_then(|index| self.visible.get(index))
+        else {
+            return;
+        };
+        let post = &self.posts[*index];
+
+        match Thread::load(&post.instance, post.id) {
+            Ok(Some(thread)) if thread.latest().is_none() => {
+                self.message = Some("Der Thread hat keine Revision.".to_string())
+            }
+            Ok(Some(thread)) => {
+                let mut view = ThreadView {
+                    thread,
+                    collapsed: HashSet::new(),
+                    rows: Vec::new(),
+                    state: ListState::default(),
+                };
+                view.refresh_rows();
+                view.state.select((!view.rows.is_empty()).then_some(0));
+                self.thread = Some(view);
+                self.focus = Focus::Thread;
+            }
+            Ok(None) => self.message = Some("Der Post ist nicht mehr im Archiv.".to_string()),
+            Err(err) => self.message = Some(err),
+        }
+    }
+
+    fn toggle_comment(&mut self) {
+        let Some(view) = &mut self.thread else {
+            return;
+        };
+        let Some(&(index, _)) = view.state.selected().and_then(|row| view.rows.get(row)) else {
+            return;
+        };
+
+        let Some(revision) = view.thread.latest() else {
+            return;
+        };
+        let id = revision.comments[index].id;
+        if !view.collapsed.remove(&id) {
+            view.collapsed.insert(id);
+        }
+        view.refresh_rows();
+    }
+
+    fn collapse_all(&mut self, collapse: bool) {
+        let Some(view) = &mut self.thread else {
+            return;
+        };
+
+        view.collapsed.clear();
+        if let Some(revision) = view.thread.latest().filter(|_| collapse) {
+            view.collapsed
+                .extend(revision.replies(None).map(|comment| comment.id));
+        }
+        view.refresh_rows();
+        view.state.select((!view.rows.is_empty()).then_some(0));
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [status, main, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [sidebar, content] =
+            Layout::horizontal([Constraint::Percentage(25), Constraint::Min(0)]).areas(main);
+
+        frame.render_widget(Paragraph::new(self.status_line()).reversed(), status);
+        self.draw_sidebar(frame, sidebar);
+        if self.thread.is_some() {
+            self.draw_thread(frame, content);
+        } else {
+            self.draw_posts(frame, content);
+        }
+        frame.render_widget(Paragraph::new(self.footer_line()), footer);
+    }
+
+    fn status_line(&self) -> String {
+        match &self.status {
+            None => " Der Daemon läuft nicht.".to_string(),
+            Some(status) => format!(
+                " Daemon {} | Warteschlange: {} | Archiviert: {} ({} Fehler) | Pausiert: {}",
+                status.version,
+                status.queue,
+                status.archived,
+                status.archive_errors,
+                if status.paused.is_empty() {
+                    "nichts".to_string()
+                } else {
+                    status.paused.join(", ")
+                }
+            ),
+        }
+    }
+
+    fn footer_line(&self) -> Line<'_> {
+        if let Some((kind, text)) = &self.input {
+            let label = match kind {
+                InputKind::Filter => "Filter",
+                InputKind::Search => "Suche",
+            };
+            return Line::from(vec![
+                Span::from(format!(" {}: ", label)).bold(),
+                Span::from(text.clone()),
+                Span::from("_").slow_blink(),
+            ]);
+        }
+        if let Some(message) = &self.message {
+            return Line::from(format!(" {}", message));
+        }
+
+        let help = match self.focus {
+            Focus::Thread => {
+                " ↑↓ Kommentar | Enter ein-/ausklappen | c alle einklappen | e alle ausklappen | Esc zurück"
+            }
+            _ => " Tab Bereich | ↑↓ auswählen | Enter öffnen | / Suche | f Filter | s Sortierung | Esc zurücksetzen | q beenden",
+        };
+        Line::from(help).dim()
+    }
+
+    fn draw_sidebar(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .sources
+            .iter()
+            .map(|source| match source {
+                Source::All => ListItem::new(format!("Alle ({})", self.posts.len())),
+                Source::Instance(instance) => ListItem::new(instance.clone().bold()),
+                Source::Community(_, community) => ListItem::new(format!("  {}", community)),
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(pane("Instanzen", self.focus == Focus::Sidebar))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, area, &mut self.sources_state);
+    }
+
+    fn draw_posts(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .visible
+            .iter()
+            .map(|index| {
+                let post = &self.posts[*index];
+                let mut title = vec![Span::from(post.title.clone()).bold()];
+                if let Some(label) = post.removal {
+                    title.insert(0, Span::from(format!("[{}] ", label)).red());
+                }
+                ListItem::new(vec![
+                    Line::from(title),
+                    Line::from(format!(
+                        "  {} | {} | Score {} | {} Kommentare | {}",
+                        post.community,
+                        post.author,
+                        post.score,
+                        post.comments,
+                        post.published
+                            .with_timezone(&Local)
+                            .format("%d.%m.%Y %H:%M")
+                    ))
+                    .dim(),
+                ])
+            })
+            .collect();
+
+        let sort = match self.sort {
+            Sort::New => "neuste",
+            Sort::Old => "älteste",
+            Sort::Score => "Score",
+        };
+        let mut title = format!("Posts ({}, {} zuerst)", self.visible.len(), sort);
+        if let Some((text, _)) = &self.search {
+            title.push_str(&format!(", Suche \"{}\"", text));
+        }
+        if !self.filter.is_empty() {
+            title.push_str(&format!(", Filter \"{}\"", self.filter));
+        }
+
+        let list = List::new(items)
+            .block(pane(&title, self.focus == Focus::Posts))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, area, &mut self.posts_state);
+    }
+
+    fn draw_thread(&mut self, frame: &mut Frame, area: Rect) {
+        let Some(view) = &mut self.thread else {
+            return;
+        };
+        let Some(revision) = view.thread.latest() else {
+            return;
+        };
+        let post = &revision.post;
+
+        let mut header = vec![
+            Line::from(post.title.clone()).bold(),
+            Line::from(format!(
+                "{} von {} auf {} | Score {} | {}",
+                post.community,
+                post.author,
+                view.thread.instance,
+                post.score,
+                post.published
+                    .with_timezone(&Local)
+                    .format("%d.%m.%Y %H:%M")
+            ))
+            .dim(),
+        ];
+        if let Some(url) = &post.url {
+            header.push(Line::from(url.clone()).underlined());
+        }
+        if let Some(body) = &post.body {
+            header.push(Line::default());
+            header.extend(body.lines().map(|line| Line::from(line.to_string())));
+        }
+
+        let height = (header.len() as u16 + 2).min(area.height / 2);
+        let [post_area, comments_area] =
+            Layout::vertical([Constraint::Length(height), Constraint::Min(0)]).areas(area);
+
+        frame.render_widget(
+            Paragraph::new(header)
+                .wrap(Wrap { trim: false })
+                .block(pane(
+                    &format!("{}/{}", view.thread.instance, view.thread.id),
+                    false,
+                )),
+            post_area,
+        );
+
+        let items: Vec<ListItem> = view
+            .rows
+            .iter()
+            .map(|&(index, depth)| comment_item(revision, &view.collapsed, index, depth))
+            .collect();
+        let list = List::new(items)
+            .block(pane(
+                &format!("{} Kommentare", revision.comments.len()),
+                true,
+            ))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, comments_area, &mut view.state);
+    }
+}
+
+impl ThreadView {
+    /// Berechnet welche Kommentare sichtbar sind, Antworten auf eingeklappte Kommentare nicht
+    fn refresh_rows(&mut self) {
+        fn visit(
+            revision: &Revision,
+            collapsed: &HashSet<i32>,
+            parent: Option<i32>,
+            depth: usize,
+            rows: &mut Vec<(usize, usize)>,
+        ) {
+            for comment in revision.replies(parent) {
+                let index = revision
+                    .comments
+                    .iter()
+                    .position(|other| other.id == comment.id)
+                    .unwrap_or_default();
+                rows.push((index, depth));
+                if !collapsed.contains(&comment.id) {
+                    visit(revision, collapsed, Some(comment.id), depth + 1, rows);
+                }
+            }
+        }
+
+        self.rows.clear();
+        if let Some(revision) = self.thread.latest() {
+            visit(revision, &self.collapsed, None, 0, &mut self.rows);
+        }
+    }
+}
+
+fn comment_item<'a>(
+    revision: &'a Revision,
+    collapsed: &HashSet<i32>,
+    index: usize,
+    depth: usize,
+) -> ListItem<'a> {
+    let comment = &revision.comments[index];
+    let indent = "│ ".repeat(depth);
+    let replies = revision.replies(Some(comment.id)).count();
+
+    let mut header = vec![
+        Span::from(indent.clone()).dim(),
+        Span::from(comment.author.clone()).bold(),
+        Span::from(format!(
+            " | Score {} | {}",
+            comment.score,
+            comment
+                .published
+                .with_timezone(&Local)
+                .format("%d.%m.%Y %H:%M")
+        ))
+        .dim(),
+    ];
+    if let Some(label) = removal_label(comment.deleted, comment.removed) {
+        header.insert(1, Span::from(format!("[{}] ", label)).red());
+    }
+
+    if collapsed.contains(&comment.id) {
+        header.push(Span::from(format!(" [+{} Antworten]", replies)).fg(Color::Yellow));
+        return ListItem::new(Line::from(header));
+    }
+
+    let mut lines = vec![Line::from(header)];
+    lines.extend(
+        comment
+            .body
+            .lines()
+            .map(|line| Line::from(format!("{}{}", indent, line))),
+    );
+    ListItem::new(lines)
+}
+
+fn pane(title: &str, focused: bool) -> Block<'static> {
+    let block = Block::bordered().title(format!(" {} ", title));
+    if focused {
+        block.border_style(Style::new().fg(Color::Cyan))
+    } else {
+        block
+    }
+}
+
+/// Holt den Status vom Daemon, ohne bei Fehlern zu exiten wie `request_from_daemon`
+fn fetch_status() -> Option<Status> {
+    if !daemon_running() {
+        return None;
+    }
+
+    let mut stream = TcpStream::connect(get(&paths().socket_file())).ok()?;
+    stream.set_read_timeout(Some(Duration::from_secs(1))).ok()?;
+    stream.write_all(b"status").ok()?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).ok()?;
+    serde_json::from_str(&response).ok()
+}
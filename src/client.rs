@@ -1,21 +1,56 @@
-use clap::{ArgAction, Parser, Subcommand};
+use chrono::{DateTime, Local};
+use clap::{ArgAction, CommandFactory, Parser, Subcommand};
+use clap_complete::{engine::ArgValueCandidates, env::CompleteEnv, Shell};
 use std::{
-    fs::{create_dir, remove_dir_all, remove_file, File},
-    io::{BufRead, BufReader, Write},
-    net::TcpStream,
+    env,
+    fs::{create_dir_all, remove_dir_all, remove_file, File},
+    io::{stdin, BufRead, BufReader, Read, Write},
+    net::{Shutdown, TcpStream},
     os::fd::IntoRawFd,
-    path::Path,
+    path::{Path, PathBuf},
     process::{exit, Command},
-    time::Duration,
+    thread,
+    time::{Duration, Instant},
 };
 
+use browse::browse;
+use completions::completions;
+use config::{paths, user_mode, Config};
+use export::{export, Filter, Format};
 use helpers::{
-    chmod, command_output_formater, daemon_running, feddit_archivieren_assert, get,
-    read_from_stream, read_pid_file, root, run_command, update,
+    chmod, command_output_formater, daemon_running, daemon_running_at, get, make_install_target,
+    parse_duration, parse_time, privileged, read_from_stream, read_pid_file, root, run_command,
+    update,
 };
+use log::Level;
+use logs::logs;
+use output::{ensure, fail, ErrorCode};
+use regex::Regex;
+use search::{search, SearchHit, SearchQuery, Sort};
+use semver::Version;
+use serde_json::json;
+use settings::{OVERRIDES_VAR, PROFILE_VAR, USER_MODE_VAR};
+use show::{find_thread, show};
+use stats::{collect, print_stats, Period};
+use status::Status;
 
+mod archive;
+mod browse;
+mod completions;
+mod config;
+mod export;
 mod helpers;
+mod lemmy;
+mod log;
+mod logs;
+mod output;
+mod rules;
+mod search;
 mod settings;
+mod show;
+mod stats;
+mod status;
+mod versions;
 
 #[derive(Subcommand)]
 enum Commands {
@@ -23,18 +58,176 @@ enum Commands {
     Start,
     /// Killt den Daemon (ohne Daten zu sichern)
     Kill,
-    /// Updated das Programm auf die neuste Version
-    Update,
+    /// Updated das Programm auf die neuste Version, auf ältere Versionen nur mit --force
+    Update {
+        /// Wechselt vorher zu diesem Updatekanal (z.B. stable oder dev) und bleibt dort
+        #[arg(long)]
+        channel: Option<String>,
+        /// Bleibt ab jetzt auf dieser Version (z.B. 0.0.46), der Daemon updatet dann nicht mehr
+        #[arg(long, conflicts_with = "unpin")]
+        pin: Option<Version>,
+        /// Folgt wieder dem Updatekanal
+        #[arg(long)]
+        unpin: bool,
+        /// Installiert diese Version nie, z.B. weil sie bekannt kaputt ist
+        #[arg(long, value_name = "VERSION")]
+        skip: Option<Version>,
+    },
+    /// Installiert wieder eine vorher installierte Version, ohne Angabe die zuletzt ersetzte
+    Rollback {
+        /// Die Version, z.B. 0.0.45
+        #[arg(value_name = "VERSION")]
+        target: Option<Version>,
+        /// Zeigt nur die aufgehobenen Versionen an
+        #[arg(long, conflicts_with = "target")]
+        list: bool,
+    },
     /// Löscht alle Dateien vom Programm, bis auf die binarys
     Clean,
     /// Zeigt Informationen über den Daemon an
-    Info,
+    Info {
+        /// Zeigt den Daemon jedes Profils an
+        #[arg(long)]
+        all: bool,
+    },
+    /// Zeigt an was der laufende Daemon gerade macht
+    Status,
+    /// Pausiert das Crawlen und Archivieren, ohne den Daemon zu stoppen
+    Pause {
+        /// Pausiert nur diese Instanz (z.B. feddit.de)
+        #[arg(long, conflicts_with = "community", add = ArgValueCandidates::new(completions::instances))]
+        instance: Option<String>,
+        /// Pausiert nur diese Community
+        #[arg(long, add = ArgValueCandidates::new(completions::communities))]
+        community: Option<String>,
+    },
+    /// Setzt das Crawlen und Archivieren fort, ohne Angabe wird alles fortgesetzt
+    Resume {
+        /// Setzt nur diese Instanz fort
+        #[arg(long, conflicts_with = "community", add = ArgValueCandidates::new(completions::instances))]
+        instance: Option<String>,
+        /// Setzt nur diese Community fort
+        #[arg(long, add = ArgValueCandidates::new(completions::communities))]
+        community: Option<String>,
+    },
     /// Überprüft den Gesundheitszustand des Daemons
     Checkhealth,
     /// Stoppt den Daemon (sichere Version von kill)
     Stop,
     /// Printet Live was der Daemon ausgibt
-    Listen,
+    Listen {
+        /// Zeigt zuerst alle Ausgaben der letzten Zeitspanne an (z.B. 30s, 10m, 2h)
+        #[arg(long)]
+        since: Option<String>,
+        /// Zeigt zuerst die letzten N Ausgaben an
+        #[arg(long)]
+        last: Option<usize>,
+    },
+    /// Zeigt die Logs des Daemons an, auch die von früheren Läufen
+    Logs {
+        /// Wartet auf neue Einträge, auch über Neustarts des Daemons hinweg
+        #[arg(short = 'F', long)]
+        follow: bool,
+        /// Zeigt nur Einträge mit mindestens diesem Level an
+        #[arg(long, value_enum)]
+        level: Option<Level>,
+        /// Zeigt nur Einträge dieser Komponente an (z.B. control, updater, daemon)
+        #[arg(long)]
+        component: Option<String>,
+        /// Zeigt nur Einträge ab diesem Zeitpunkt an (z.B. 1h oder 2024-03-12)
+        #[arg(long)]
+        since: Option<String>,
+        /// Zeigt nur Einträge an, deren Nachricht auf diesen regulären Ausdruck passt
+        #[arg(long)]
+        grep: Option<String>,
+    },
+    /// Öffnet eine Session mit dem Daemon, liest Befehle als "<id> <befehl>" zeilenweise von stdin
+    Session,
+    /// Durchsucht die Titel, Texte und Kommentare im Archiv
+    Search {
+        /// Der gesuchte Text (Groß- und Kleinschreibung wird ignoriert)
+        query: String,
+        /// Sucht nur in dieser Community
+        #[arg(long, add = ArgValueCandidates::new(completions::communities))]
+        community: Option<String>,
+        /// Sucht nur Posts und Kommentare von diesem Nutzer
+        #[arg(long)]
+        author: Option<String>,
+        /// Sucht nur auf dieser Instanz (z.B. feddit.de)
+        #[arg(long, add = ArgValueCandidates::new(completions::instances))]
+        instance: Option<String>,
+        /// Nur Treffer ab diesem Zeitpunkt (z.B. 2024-03-12 oder 7d)
+        #[arg(long)]
+        since: Option<String>,
+        /// Nur Treffer bis zu diesem Zeitpunkt
+        #[arg(long)]
+        until: Option<String>,
+        /// Nur Treffer mit mindestens diesem Score
+        #[arg(long, allow_negative_numbers = true)]
+        min_score: Option<i64>,
+        /// Sortierung der Treffer
+        #[arg(long, value_enum, default_value = "new")]
+        sort: Sort,
+        /// Zeigt maximal so viele Treffer an
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Exportiert das Archiv als JSON, CSV oder Markdown
+    Export {
+        /// Das Format des Exports
+        #[arg(long, value_enum)]
+        format: Format,
+        /// Das Verzeichnis in das exportiert wird
+        #[arg(long)]
+        out: PathBuf,
+        /// Exportiert nur diese Community
+        #[arg(long, add = ArgValueCandidates::new(completions::communities))]
+        community: Option<String>,
+        /// Exportiert nur diese Instanz
+        #[arg(long, add = ArgValueCandidates::new(completions::instances))]
+        instance: Option<String>,
+        /// Nur Posts ab diesem Zeitpunkt (z.B. 2024-03-12 oder 7d)
+        #[arg(long)]
+        since: Option<String>,
+        /// Nur Posts bis zu diesem Zeitpunkt
+        #[arg(long)]
+        until: Option<String>,
+    },
+    /// Archiviert einen Post sofort, ohne auf den Crawler zu warten
+    ArchiveNow {
+        /// Die URL eines Posts oder eines Kommentars darunter
+        url: String,
+    },
+    /// Öffnet einen Browser für das Archiv im Terminal
+    Browse,
+    /// Zeigt einen archivierten Post mit allen Kommentaren an
+    Show {
+        /// Die ID des Posts, bei Bedarf mit Instanz (z.B. feddit.de/1234)
+        #[arg(add = ArgValueCandidates::new(completions::posts))]
+        post: String,
+        /// Zeigt eine ältere Revision an (1 ist die älteste)
+        #[arg(long)]
+        revision: Option<usize>,
+    },
+    /// Zeigt Statistiken über das Archiv und den Crawler an
+    Stats {
+        /// Der Zeitraum nach dem das Wachstum gruppiert wird
+        #[arg(long, value_enum, default_value = "day")]
+        per: Period,
+    },
+    /// Zeigt oder ändert die Konfiguration des Daemons
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// Lädt die Konfiguration im laufenden Daemon neu (wie SIGHUP)
+    Reload,
+    /// Printet das Skript für die Vervollständigung in der Shell, z.B. für
+    /// `source <(feddit_archivieren completions zsh)`
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
     /// Deinstalliert das Programm (ruft auch Clean)
     Uninstall,
     /// (DEBUG) Installiert das Programm
@@ -43,8 +236,23 @@ enum Commands {
     UpdateLocal,
     /// (DEBUG) Killt den Daemon wenn er läuft
     KillMaybe,
-    /// (DEBUG) Zeigt die Logs des Daemons an
-    LogsStatic,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Zeigt die ganze Konfiguration an, inklusive Standardwerten
+    Show {
+        /// Zeigt jeden Wert einzeln mit seiner Herkunft an (Standardwert, Datei, Umgebungsvariable
+        /// oder --set)
+        #[arg(long, action = ArgAction::SetTrue)]
+        effective: bool,
+    },
+    /// Zeigt den Wert eines Schlüssels an (z.B. update.interval)
+    Get { key: String },
+    /// Setzt einen Schlüssel, der Daemon lädt die Konfiguration danach neu
+    Set { key: String, value: String },
+    /// Überprüft die Konfigurationsdatei
+    Validate,
 }
 
 #[derive(Parser)]
@@ -62,315 +270,993 @@ struct Cli {
     /// binarys in target/debug oder target/release befinden.
     #[arg(short, long, action = ArgAction::SetTrue, global = true)]
     dev_build: bool,
+
+    /// Verwendet ein eigenes Profil mit eigener Konfiguration, eigenem Daemon und eigenem Archiv.
+    /// Geht auch mit FEDDIT_ARCHIVIEREN_PROFILE=<name>.
+    #[arg(long, global = true, value_name = "NAME", value_parser = config::parse_profile)]
+    profile: Option<String>,
+
+    /// Läuft ohne root als eigene Instanz des Nutzers, mit Pfaden aus $XDG_RUNTIME_DIR,
+    /// $XDG_DATA_HOME und $XDG_CONFIG_HOME und den binarys in ~/.local/bin. Geht auch mit
+    /// FEDDIT_ARCHIVIEREN_USER=1.
+    #[arg(long, action = ArgAction::SetTrue, global = true)]
+    user: bool,
+
+    /// Überschreibt einen Wert der Konfiguration, z.B. --set update.interval=2h. Geht mehrmals und
+    /// hat Vorrang vor der Konfigurationsdatei und Umgebungsvariablen wie
+    /// FEDDIT_ARCHIVIEREN_UPDATE_INTERVAL=2h.
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE", value_parser = config::parse_override)]
+    overrides: Vec<String>,
+
+    /// Gibt das Ergebnis als ein JSON-Objekt mit stabilen Feldern und Fehlercodes aus. Befehle die
+    /// fortlaufend ausgeben (listen, session, logs --follow) geben ein JSON-Objekt pro Zeile aus.
+    #[arg(long, action = ArgAction::SetTrue, global = true)]
+    json: bool,
 }
 
 fn main() {
+    // Ruft die Shell zum Vervollständigen auf, wird das hier beantwortet und beendet
+    CompleteEnv::with_factory(Cli::command)
+        .var(completions::COMPLETE_VAR)
+        .complete();
+
     let args = Cli::parse();
+    // Über die Umgebung erben auch der Daemon und `make` den Benutzermodus und das Profil
+    if args.user {
+        env::set_var(USER_MODE_VAR, "1");
+    }
+    if let Some(profile) = &args.profile {
+        env::set_var(PROFILE_VAR, profile);
+    }
+    if !args.overrides.is_empty() {
+        let mut overrides = env::var(OVERRIDES_VAR).unwrap_or_default();
+        for setting in &args.overrides {
+            overrides.push_str(setting);
+            overrides.push('\n');
+        }
+        env::set_var(OVERRIDES_VAR, overrides);
+    }
     let force = args.force;
     let dev_build = args.dev_build;
+    output::set_json(args.json);
+    config::on_invalid(invalid_config);
+    // Mit einer ungültigen Konfiguration muss sich die Konfiguration trotzdem reparieren lassen
+    if !matches!(
+        args.subcommand,
+        Commands::Config { .. } | Commands::Completions { .. }
+    ) {
+        config::init(load_config_or_exit());
+    }
 
     match args.subcommand {
         Commands::Install => {
             let mut replace_daemon = false;
             if daemon_running() {
                 if force {
-                    println!("Force-Kille den Daemon...");
+                    say!("Force-Kille den Daemon...");
                     kill_daemon();
                 } else {
-                    if let Err(err) = set_stdio_to_file(settings::UPDATE_LOG_FILE) {
-                        println!("Fehler beim Setzen von Stdio: {}", err);
+                    if let Err(err) = set_stdio_to_file(&paths().update_log_file()) {
+                        say_err!("Fehler beim Setzen von Stdio: {}", err);
                     }
-                    println!("Es laeuft bereits ein Daemon, versuche ihn zu restarten mit der neuen Version...");
+                    say!("Es laeuft bereits ein Daemon, versuche ihn zu restarten mit der neuen Version...");
                     replace_daemon = true;
                     if let Err(err) = restart_daemon() {
-                        println!("Fehler beim Stoppen des Daemons: {}", err);
-                        exit(1);
-                    } else {
-                        println!("test");
+                        fail(
+                            ErrorCode::DaemonUnhealthy,
+                            &format!("Fehler beim Stoppen des Daemons: {}", err),
+                        );
                     }
                     if !daemon_running() {
-                        println!("Gestoppt!");
+                        say!("Gestoppt!");
                     }
                 }
             }
 
+            // Im Benutzermodus existiert z.B. ~/.local/bin vielleicht noch nicht
+            for binary in [&paths().daemon_binary, &paths().client_binary] {
+                create_parent_dir(binary);
+            }
+
+            // Die installierte Version für `rollback` aufheben
+            let previous = match versions::retain(config::current().update.keep_versions, None) {
+                Ok(previous) => previous,
+                Err(err) => {
+                    say_err!("Fehler beim Aufheben der alten Version: {}", err);
+                    None
+                }
+            };
+
             // Die alten Binarys löschen
-            remove_if_existing(settings::DAEMON_PATH);
-            remove_if_existing(settings::CLIENT_PATH);
+            remove_if_existing(&paths().daemon_binary);
+            remove_if_existing(&paths().client_binary);
 
             // Die neuen an die richtige Stelle kopieren
             if dev_build {
-                copy_file("target/debug/daemon", settings::DAEMON_PATH);
-                copy_file("target/debug/client", settings::CLIENT_PATH);
+                copy_file("target/debug/daemon", &paths().daemon_binary);
+                copy_file("target/debug/client", &paths().client_binary);
             } else {
-                copy_file("target/release/daemon", settings::DAEMON_PATH);
-                copy_file("target/release/client", settings::CLIENT_PATH);
+                copy_file("target/release/daemon", &paths().daemon_binary);
+                copy_file("target/release/client", &paths().client_binary);
             }
 
-            // Das Update und Run-Verzeichnis erstellen
+            // Das Update, Run und Daten-Verzeichnis erstellen
             create_run_dir();
+            create_data_dir();
 
-            if !Path::new(settings::UDPATE_DIR).exists() {
-                if let Err(err) = create_dir(settings::UDPATE_DIR) {
-                    let msg = &format!(
-                        "Fehler beim Erstellen von {}: {}",
-                        settings::UDPATE_DIR,
-                        err
-                    );
-                    if replace_daemon {
-                        println!("{}", msg);
-                    } else {
-                        eprintln!("{}", msg);
-                    };
+            if !Path::new(&paths().update_dir).exists() {
+                if let Err(err) = create_dir_all(&paths().update_dir) {
+                    say_err!("Fehler beim Erstellen von {}: {}", &paths().update_dir, err);
                 }
             }
 
             // Jedem Benutzer read-write-execute Rechte für die Dateien geben, wenn möglich
             if root() {
-                chmod(settings::DAEMON_PATH, "777");
-                chmod(settings::CLIENT_PATH, "777");
+                chmod(&paths().daemon_binary, "777");
+                chmod(&paths().client_binary, "777");
             }
 
-            println!("Installation erfolgreich!");
+            say!("Installation erfolgreich!");
 
+            // Nach einem Update wird zurückgerollt, wenn die neue Version nicht gesund ist
+            let mut rolled_back = None;
             if replace_daemon {
-                println!("Starte den Daemon neu...");
-                start_daemon();
+                say!("Starte den Daemon neu...");
+                let grace = Duration::from_secs(config::current().update.health_grace);
+                if let Err(err) = try_start_daemon().and_then(|_| watch_health(grace)) {
+                    let failed = versions::installed()
+                        .unwrap_or_else(|| Version::parse(env!("CARGO_PKG_VERSION")).unwrap());
+                    rolled_back = Some(roll_back_failed_install(failed, previous, &err));
+                }
             }
+            output::done(json!({
+                "installed": true,
+                "restarted": replace_daemon,
+                "rolled_back": rolled_back,
+            }));
         }
         Commands::Start => {
             if daemon_running() {
                 if force {
                     kill_daemon();
                 } else {
-                    eprintln!("Der Daemon läuft bereits.");
-                    exit(1);
+                    fail(ErrorCode::DaemonRunning, "Der Daemon läuft bereits.");
                 }
             }
 
             start_daemon();
+            output::done(json!({ "started": true }));
         }
         Commands::Kill => {
             kill_daemon();
+            output::done(json!({ "killed": true }));
         }
         Commands::KillMaybe => {
-            if daemon_running() {
+            let running = daemon_running();
+            if running {
                 kill_daemon();
             }
+            output::done(json!({ "killed": running }));
         }
-        Commands::Update => {
-            if force && daemon_running() {
+        Commands::Update {
+            channel,
+            pin,
+            unpin,
+            skip,
+        } => {
+            // --pin, --unpin und --skip ändern nur die Konfiguration, dafür muss weder der Daemon
+            // gestoppt noch etwas gebaut werden
+            let config_only = channel.is_none() && (pin.is_some() || unpin || skip.is_some());
+
+            let mut source = config::current().update.clone();
+            if let Some(pin) = pin {
+                match config::set("update.pin", &format!("\"{}\"", pin)) {
+                    Ok(config) => source = config.update,
+                    Err(err) => fail(ErrorCode::InvalidConfig, &err),
+                }
+                say!("Bleibe ab jetzt auf Version {}.", pin);
+            }
+            if unpin {
+                match config::unset("update.pin") {
+                    Ok(config) => source = config.update,
+                    Err(err) => fail(ErrorCode::InvalidConfig, &err),
+                }
+                say!("Folge wieder dem Kanal {}.", source.channel);
+            }
+            if let Some(skip) = skip {
+                match config::skip_version(&skip) {
+                    Ok(config) => source = config.update,
+                    Err(err) => fail(ErrorCode::InvalidConfig, &err),
+                }
+                say!("Version {} wird nie installiert.", skip);
+            }
+
+            if config_only {
+                let running = daemon_running();
+                if running {
+                    reload_daemon();
+                }
+                output::done(json!({
+                    "updated": false,
+                    "channel": source.channel,
+                    "pin": source.pin,
+                    "reloaded": running,
+                }));
+                return;
+            }
+
+            // Mit --force wird ein laufender Daemon gekillt und nach dem Update wieder gestartet
+            let was_running = daemon_running();
+            if force && was_running {
                 kill_daemon();
             } else {
-                feddit_archivieren_assert(!daemon_running(), "Der Daemon läuft gerade.");
-                feddit_archivieren_assert(root(), "Du must root sein.");
+                ensure(
+                    !was_running,
+                    ErrorCode::DaemonRunning,
+                    "Der Daemon läuft gerade.",
+                );
+                ensure(privileged(), ErrorCode::NotRoot, "Du must root sein.");
             }
 
-            // Die Update Funktion rufen, auf das Ergebnis reagieren
-            if let Err(message) = update(None, None) {
-                eprintln!("Fehler beim Updaten: ");
-                eprintln!("{}", message);
-                exit(1);
+            if let Some(channel) = channel {
+                ensure(
+                    source.channels.contains_key(&channel),
+                    ErrorCode::InvalidArgument,
+                    &format!(
+                        "Unbekannter Updatekanal \"{}\", bekannt sind: {}",
+                        channel,
+                        source
+                            .channels
+                            .keys()
+                            .cloned()
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                );
+                match config::set("update.channel", &channel) {
+                    Ok(config) => source = config.update,
+                    Err(err) => fail(ErrorCode::InvalidConfig, &err),
+                }
+                say!("Folge ab jetzt dem Kanal {}.", channel);
             }
 
-            exit(0);
+            // Die Update Funktion rufen, auf das Ergebnis reagieren. Mit --json wird der
+            // Fortschritt verschluckt.
+            let previous = versions::installed();
+            let result = if output::json() {
+                update(&source, force, false, Some(|_, ()| {}), Some(()))
+            } else {
+                update::<()>(&source, force, false, None, None)
+            };
+            let installed = match result {
+                Ok(installed) => installed,
+                Err(message) => fail(
+                    ErrorCode::UpdateFailed,
+                    &format!("Fehler beim Updaten: \n{}", message),
+                ),
+            };
+
+            // Wie bei `install` wird die neue Version beobachtet und zurückgerollt, wenn sie nicht
+            // gesund ist. Lief kein Daemon, wird sie erst beim nächsten Start benutzt.
+            let mut rolled_back = None;
+            if installed && was_running {
+                say!("Starte den Daemon neu...");
+                let grace = Duration::from_secs(source.health_grace);
+                if let Err(err) = try_start_daemon().and_then(|_| watch_health(grace)) {
+                    match versions::installed() {
+                        Some(failed) => {
+                            rolled_back = Some(roll_back_failed_install(failed, previous, &err))
+                        }
+                        None => fail(ErrorCode::DaemonUnhealthy, &err),
+                    }
+                }
+            }
+
+            output::done(json!({
+                "updated": installed,
+                "channel": source.channel,
+                "pin": source.pin,
+                "restarted": installed && was_running,
+                "rolled_back": rolled_back,
+            }));
+        }
+        Commands::Rollback { list: true, .. } => {
+            let retained = match versions::list() {
+                Ok(retained) => retained,
+                Err(err) => fail(ErrorCode::Io, &err),
+            };
+            if retained.is_empty() {
+                say!("Es gibt keine aufgehobenen Versionen.");
+            }
+            for version in &retained {
+                say!(
+                    "{}\tersetzt am {}",
+                    version.version,
+                    DateTime::<Local>::from(version.time).format("%Y-%m-%d %H:%M")
+                );
+            }
+            output::done(json!({
+                "versions": retained
+                    .iter()
+                    .map(|version| json!({
+                        "version": version.version,
+                        "replaced": DateTime::<Local>::from(version.time).to_rfc3339(),
+                    }))
+                    .collect::<Vec<_>>(),
+            }));
+        }
+        Commands::Rollback { target, .. } => {
+            ensure(privileged(), ErrorCode::NotRoot, "Du must root sein.");
+
+            let installed = versions::installed();
+            let retained = match versions::list() {
+                Ok(retained) => retained,
+                Err(err) => fail(ErrorCode::Io, &err),
+            };
+            let version = target;
+            let target = match &version {
+                Some(version) => retained
+                    .into_iter()
+                    .find(|retained| &retained.version == version),
+                None => retained
+                    .into_iter()
+                    .find(|retained| Some(&retained.version) != installed.as_ref()),
+            };
+            let Some(target) = target else {
+                fail(
+                    ErrorCode::NotFound,
+                    &match version {
+                        Some(version) => format!("Version {} wurde nicht aufgehoben.", version),
+                        None => "Es gibt keine aufgehobene Version.".to_string(),
+                    },
+                );
+            };
+
+            let running = daemon_running();
+            if running {
+                if let Err(err) = stop_daemon() {
+                    fail(
+                        ErrorCode::DaemonUnhealthy,
+                        &format!("Fehler beim Stoppen des Daemons: {}", err),
+                    );
+                }
+            }
+
+            // Die ersetzte Version wird auch aufgehoben, damit man wieder zurück kann
+            if let Err(err) = versions::retain(
+                config::current().update.keep_versions,
+                Some(&target.version),
+            ) {
+                say_err!("Fehler beim Aufheben der alten Version: {}", err);
+            }
+            if let Err(err) = versions::restore(&target) {
+                fail(ErrorCode::InstallFailed, &err);
+            }
+            if root() {
+                chmod(&paths().daemon_binary, "777");
+                chmod(&paths().client_binary, "777");
+            }
+            say!("Version {} ist wieder installiert.", target.version);
+            if let Some(installed) = &installed {
+                say!(
+                    "Damit der Daemon nicht wieder auf {} updatet: update --skip {} oder update --pin {}",
+                    installed,
+                    installed,
+                    target.version
+                );
+            }
+
+            if running {
+                start_daemon();
+            }
+            output::done(json!({
+                "version": target.version,
+                "previous": installed,
+                "restarted": running,
+            }));
         }
         Commands::UpdateLocal => {
             if !force {
-                feddit_archivieren_assert(root(), "Du must root sein.");
+                ensure(privileged(), ErrorCode::NotRoot, "Du must root sein.");
             }
 
             if daemon_running() {
                 if let Err(err) = stop_daemon() {
-                    eprintln!("Fehler beim Stoppen des Daemons: {}", err);
+                    say_err!("Fehler beim Stoppen des Daemons: {}", err);
                 }
             }
 
-            // `make clean install` ausführen
-            println!("Compile den Source Code...");
-            match Command::new("make").arg("clean").arg("install").output() {
+            // `make clean install` ausführen, bzw. `install_user` im Benutzermodus
+            say!("Compile den Source Code...");
+            match Command::new("make")
+                .arg("clean")
+                .arg(make_install_target())
+                .output()
+            {
                 Ok(output) => {
                     if !output.status.success() {
-                        eprintln!("Fehler bei der Installation.");
-                        eprintln!("{}", command_output_formater(&output));
-                        exit(1);
+                        fail(
+                            ErrorCode::InstallFailed,
+                            &format!(
+                                "Fehler bei der Installation.\n{}",
+                                command_output_formater(&output)
+                            ),
+                        );
                     }
                 }
-                Err(err) => {
-                    eprintln!("Fehler bei der Installation: {}", err);
-                    exit(1);
-                }
+                Err(err) => fail(
+                    ErrorCode::InstallFailed,
+                    &format!("Fehler bei der Installation: {}", err),
+                ),
             }
-            println!("Lokales Update erfolgreich abgeschlossen.");
-            exit(0);
+            say!("Lokales Update erfolgreich abgeschlossen.");
+            output::done(json!({ "updated": true }));
         }
         Commands::Clean => {
-            exit(clean());
+            let exit_code = clean();
+            if exit_code == 0 {
+                output::done(json!({ "cleaned": true }));
+            } else if output::json() {
+                fail(ErrorCode::Io, "Nicht alle Dateien konnten gelöscht werden.");
+            }
+            exit(exit_code);
         }
-        Commands::Info => {
+        Commands::Info { all: true } => print_all_profiles(),
+        Commands::Info { all: false } => {
+            let running = daemon_running();
+            if output::json() {
+                output::done(json!({
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "profile": config::profile(),
+                    "channel": config::current().update.channel,
+                    "pin": config::current().update.pin,
+                    "user_mode": user_mode(),
+                    "config": config::config_file(),
+                    "running": running,
+                    "address": running.then(|| get(&paths().socket_file())),
+                    "pid": running.then(|| get(&paths().pid_file()).trim().parse::<u32>().ok()).flatten(),
+                }));
+                return;
+            }
+
             println!("Feddit-Archivieren Version {}", env!("CARGO_PKG_VERSION"));
-            if !daemon_running() {
+            if let Some(profile) = config::profile() {
+                println!("Profil:\t{}", profile);
+            }
+            let update = &config::current().update;
+            match update.current_channel() {
+                Ok(channel) => println!("Kanal:\t{} ({})", update.channel, channel),
+                Err(err) => println!("Kanal:\t{}", err),
+            }
+            if let Some(pin) = &update.pin {
+                println!("Gepinnt:\t{}", pin);
+            }
+            if user_mode() {
+                println!("Benutzermodus, Konfiguration in {}", config::config_file());
+            }
+            if !running {
                 println!("Der Daemon läuft nicht.")
             } else {
                 println!("Der Daemon läuft.");
-                println!("Port:\t{}", get(settings::SOCKET_FILE));
-                println!("PID:\t{}", get(settings::PID_FILE));
-            }
-        }
-        Commands::LogsStatic => {
-            // Printet den Inhalt von OUT_FILE und ERR_FILE
-
-            match File::open(settings::OUT_FILE) {
-                Ok(file) => {
-                    let mut iterator = BufReader::new(&file).lines().peekable();
-                    if iterator.peek().is_some() {
-                        println!("STDOUT:");
-                        while let Some(line) = iterator.next() {
-                            println!(
-                                "{}",
-                                line.unwrap_or("<FEHLER BEIM LESEN DIESER ZEILE>".into())
-                            );
-                        }
-                    }
-                }
-                Err(err) => {
-                    println!("Fehler beim Lesen von {}: {}", settings::OUT_FILE, err);
-                }
+                println!("Port:\t{}", get(&paths().socket_file()));
+                println!("PID:\t{}", get(&paths().pid_file()));
             }
-            match File::open(settings::ERR_FILE) {
-                Ok(file) => {
-                    let mut iterator = BufReader::new(&file).lines().peekable();
-                    if iterator.peek().is_some() {
-                        println!("STDERR:");
-                        while let Some(line) = iterator.next() {
-                            println!(
-                                "{}",
-                                line.unwrap_or("<FEHLER BEIM LESEN DIESER ZEILE>".into())
-                            );
-                        }
-                    }
-                }
-                Err(err) => {
-                    eprintln!("Fehler beim Lesen von {}: {}", settings::ERR_FILE, err);
-                }
+        }
+        Commands::Status => {
+            ensure_daemon_running();
+
+            let response = request_from_daemon("status");
+            let status: Status = match serde_json::from_str(&response) {
+                Ok(status) => status,
+                Err(err) => fail(
+                    ErrorCode::DaemonUnhealthy,
+                    &format!(
+                        "Der Daemon hat einen ungültigen Status gesendet: {}\n{}",
+                        err, response
+                    ),
+                ),
+            };
+
+            if output::json() {
+                output::done(&status);
+            } else {
+                print_status(&status);
+            }
+        }
+        Commands::Pause {
+            instance,
+            community,
+        } => {
+            ensure_daemon_running();
+
+            let message = scoped_message("pause", instance, community);
+            let response = request_from_daemon(&message);
+            ensure(response == "ok", ErrorCode::InvalidArgument, &response);
+            say!("Pausiert.");
+            output::done(json!({ "paused": scope_of(&message).unwrap_or("*") }));
+        }
+        Commands::Resume {
+            instance,
+            community,
+        } => {
+            ensure_daemon_running();
+
+            let message = scoped_message("resume", instance, community);
+            let response = request_from_daemon(&message);
+            ensure(response == "ok", ErrorCode::InvalidArgument, &response);
+            say!("Fortgesetzt.");
+            output::done(json!({ "resumed": scope_of(&message).unwrap_or("*") }));
+        }
+        Commands::Logs {
+            follow,
+            level,
+            component,
+            since,
+            grep,
+        } => {
+            let grep = grep.map(|grep| match Regex::new(&grep) {
+                Ok(regex) => regex,
+                Err(err) => fail(
+                    ErrorCode::InvalidArgument,
+                    &format!("Ungültiger regulärer Ausdruck: {}", err),
+                ),
+            });
+            let filter = logs::Filter {
+                level,
+                component,
+                since: since.map(|since| parse_time_or_exit(&since)),
+                grep,
+            };
+
+            if let Err(err) = logs(&filter, follow) {
+                fail(ErrorCode::Io, &err);
             }
         }
         Commands::Checkhealth => {
             if force && !daemon_running() {
                 start_daemon();
             } else {
-                feddit_archivieren_assert(daemon_running(), "Der Daemon läuft nicht.");
+                ensure_daemon_running();
             }
 
             // Schickt `ping` an den Daemon, erwartet `pong`
 
-            println!("Versuche Daten in den Stream zu schreiben.");
+            say!("Versuche Daten in den Stream zu schreiben.");
             let mut stream = send_to_daemon("ping");
-            println!("Fertig.");
+            say!("Fertig.");
 
-            println!("Versuche Daten aus dem Stream zu empfangen.");
+            say!("Versuche Daten aus dem Stream zu empfangen.");
             let message = read_from_stream(&mut stream);
 
-            feddit_archivieren_assert(
+            ensure(
                 message == "pong",
+                ErrorCode::DaemonUnhealthy,
                 format!("Nachricht pong erwartet, '{}' empfangen.", message).as_str(),
             );
-            println!("Nachricht pong erfolgreich empfangen!");
-            println!("Der Daemon scheint zu funktionieren.");
+            say!("Nachricht pong erfolgreich empfangen!");
+            say!("Der Daemon scheint zu funktionieren.");
+            output::done(json!({ "healthy": true }));
         }
         Commands::Stop => {
             if force && !daemon_running() {
                 start_daemon();
             } else {
-                feddit_archivieren_assert(daemon_running(), "Der Daemon läuft nicht.");
+                ensure_daemon_running();
             }
 
             // Sendet `stop` an den Daemon, erwartet`ok`
             if let Err(err) = stop_daemon() {
-                eprintln!("Ein Fehler ist aufgetreten:\n{}", err);
-                exit(1);
+                fail(
+                    ErrorCode::DaemonUnhealthy,
+                    &format!("Ein Fehler ist aufgetreten:\n{}", err),
+                );
             }
 
-            println!("Der Daemon wurde erfolgreich beendet!");
+            say!("Der Daemon wurde erfolgreich beendet!");
+            output::done(json!({ "stopped": true }));
         }
-        Commands::Listen => {
+        Commands::Listen { since, last } => {
             if force && !daemon_running() {
                 start_daemon();
             } else {
-                feddit_archivieren_assert(daemon_running(), "Der Daemon läuft nicht.");
+                ensure_daemon_running();
+            }
+
+            let mut message = String::from("listen");
+            if let Some(since) = since {
+                match parse_duration(&since) {
+                    Ok(since) => message.push_str(&format!(" since={}", since.as_secs())),
+                    Err(err) => fail(ErrorCode::InvalidArgument, &err),
+                }
+            }
+            if let Some(last) = last {
+                message.push_str(&format!(" last={}", last));
             }
 
             // Sendet `listen` an den Daemon, printet alles was empfangen wird
-            let mut stream = send_to_daemon("listen");
+            let mut lines = BufReader::new(send_to_daemon(&message)).lines();
+            // Nur die erste Zeile einer Verbindung kann eine Ablehnung sein, danach kommen Ausgaben
+            let mut handshake = true;
             loop {
-                let response = read_from_stream(&mut stream);
-                if response.is_empty() {
-                    println!("Der Daemon hat die Verbindung geschlossen.");
-                    exit(0);
+                let response = match lines.next() {
+                    Some(Ok(response)) => response,
+                    _ => {
+                        say!("Der Daemon hat die Verbindung geschlossen.");
+                        exit(0);
+                    }
+                };
+                if handshake {
+                    exit_if_rejected(&response);
+                    handshake = false;
                 }
                 if response.to_lowercase().trim() == "restart" {
-                    println!("Der Daemon wird neu gestartet.");
+                    say!("Der Daemon wird neu gestartet.");
                     if daemon_running() {
                         if wait_with_timeout!(|| !daemon_running(), Duration::from_millis(500)) {
-                            println!("Der Daemon wurde gestoppt.");
+                            say!("Der Daemon wurde gestoppt.");
                         } else {
-                            println!("Der Daemon wurde innerhalb von 0.5 Sekunden nicht beendet.");
-                            exit(1);
+                            fail(
+                                ErrorCode::DaemonUnhealthy,
+                                "Der Daemon wurde innerhalb von 0.5 Sekunden nicht beendet.",
+                            );
                         }
                     }
 
-                    if wait_with_timeout!(|| daemon_running(), Duration::from_secs(5)) {
-                        println!("Der Daemon ist wieder online!");
+                    if wait_with_timeout!(daemon_running, Duration::from_secs(5)) {
+                        say!("Der Daemon ist wieder online!");
                     } else {
-                        println!(
-                            "Der Daemon ist innerhalb von 5 Sekunden nicht wieder online gegangen."
+                        fail(
+                            ErrorCode::DaemonUnhealthy,
+                            "Der Daemon ist innerhalb von 5 Sekunden nicht wieder online gegangen.",
                         );
-                        exit(1);
                     }
 
-                    if wait_with_timeout!(|| daemon_ready(), Duration::from_secs(1)) {
-                        println!("Der Daemon ist bereit Verbindungen zu empfangen!");
+                    if wait_with_timeout!(daemon_ready, Duration::from_secs(1)) {
+                        say!("Der Daemon ist bereit Verbindungen zu empfangen!");
                     } else {
-                        println!("Der Daemon ist 1 Sekunde nach Start immer noch nicht bereit Verbindungen zu empfangen.");
-                        exit(1)
+                        fail(ErrorCode::DaemonUnhealthy, "Der Daemon ist 1 Sekunde nach Start immer noch nicht bereit Verbindungen zu empfangen.");
                     }
 
-                    println!("Stelle Verbindung wieder her...");
-                    stream = send_to_daemon("listen");
+                    say!("Stelle Verbindung wieder her...");
+                    lines = BufReader::new(send_to_daemon("listen")).lines();
+                    handshake = true;
+                } else if output::json() {
+                    output::line(json!({ "message": response }));
                 } else {
                     println!("{}", response);
                 }
             }
         }
+        Commands::Session => {
+            ensure_daemon_running();
+
+            let stream = send_to_daemon("session\n");
+            let mut writer = match stream.try_clone() {
+                Ok(writer) => writer,
+                Err(err) => fail(
+                    ErrorCode::ConnectionFailed,
+                    &format!("Fehler beim Klonen des Streams: {}", err),
+                ),
+            };
+
+            // Befehle von stdin an den Daemon weiterleiten, während die Antworten ausgegeben werden
+            thread::spawn(move || {
+                for line in stdin().lock().lines().map_while(Result::ok) {
+                    if let Err(err) = writeln!(writer, "{}", line) {
+                        eprintln!("Fehler beim Senden an den Daemon: {}", err);
+                        break;
+                    }
+                }
+                let _ = writer.shutdown(Shutdown::Write);
+            });
+
+            for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                if output::json() {
+                    let (id, response) = line.split_once(' ').unwrap_or((&line, ""));
+                    output::line(json!({ "id": id, "response": response }));
+                } else {
+                    println!("{}", line);
+                }
+            }
+        }
+        Commands::Search {
+            query,
+            community,
+            author,
+            instance,
+            since,
+            until,
+            min_score,
+            sort,
+            limit,
+        } => {
+            let query = SearchQuery {
+                text: query,
+                community,
+                author,
+                instance,
+                since: since.map(|since| parse_time_or_exit(&since)),
+                until: until.map(|until| parse_time_or_exit(&until)),
+                min_score,
+                sort,
+                limit,
+            };
+
+            // Läuft der Daemon, sucht er selbst, sonst werden die Dateien direkt gelesen
+            let hits: Result<Vec<SearchHit>, String> = if daemon_running() {
+                search_with_daemon(&query)
+            } else {
+                search(&query, |err| {
+                    eprintln!("Warnung: Überspringe Thread: {}", err)
+                })
+            };
+
+            match hits {
+                Ok(hits) if output::json() => output::done(json!({ "hits": hits })),
+                Ok(hits) => print_search_hits(&hits),
+                Err(err) => fail(
+                    ErrorCode::SearchFailed,
+                    &format!("Fehler bei der Suche: {}", err),
+                ),
+            }
+        }
+        Commands::Export {
+            format,
+            out,
+            community,
+            instance,
+            since,
+            until,
+        } => {
+            let filter = Filter {
+                community,
+                instance,
+                since: since.map(|since| parse_time_or_exit(&since)),
+                until: until.map(|until| parse_time_or_exit(&until)),
+            };
+
+            let mut skipped = 0;
+            let result = export(format, &filter, &out, |err| {
+                eprintln!("Warnung: Überspringe Thread: {}", err);
+                skipped += 1;
+            });
+            match result {
+                Ok(count) => {
+                    say!("{} Posts nach {:?} exportiert.", count, out);
+                    if skipped > 0 {
+                        say!("{} Threads konnten nicht gelesen werden.", skipped);
+                    }
+                    output::done(json!({ "posts": count, "out": out, "skipped": skipped }));
+                }
+                Err(err) => fail(
+                    ErrorCode::ExportFailed,
+                    &format!("Fehler beim Exportieren: {}", err),
+                ),
+            }
+        }
+        Commands::Browse => {
+            ensure(
+                !output::json(),
+                ErrorCode::InvalidArgument,
+                "browse ist interaktiv und unterstützt --json nicht.",
+            );
+            if let Err(err) = browse() {
+                fail(ErrorCode::Io, &err);
+            }
+        }
+        Commands::Show { post, revision } => {
+            let thread = match find_thread(&post) {
+                Ok(thread) => thread,
+                Err(err) => fail(ErrorCode::NotFound, &err),
+            };
+
+            if output::json() {
+                let number = revision.unwrap_or(thread.revisions.len());
+                match number
+                    .checked_sub(1)
+                    .and_then(|index| thread.revisions.get(index))
+                {
+                    Some(found) => output::done(json!({
+                        "instance": thread.instance,
+                        "id": thread.id,
+                        "revision": number,
+                        "revisions": thread.revisions.len(),
+                        "archived": found.archived,
+                        "post": found.post,
+                        "comments": found.comments,
+                    })),
+                    None => fail(
+                        ErrorCode::NotFound,
+                        &format!("Revision {} existiert nicht.", number),
+                    ),
+                }
+            } else if let Err(err) = show(&thread, revision) {
+                fail(ErrorCode::NotFound, &err);
+            }
+        }
+        Commands::ArchiveNow { url } => {
+            ensure_daemon_running();
+
+            // Der Daemon schickt den Fortschritt zeilenweise, die letzte Zeile ist das Ergebnis
+            let stream = send_to_daemon(&format!("archive-now {}", url));
+            let mut steps = Vec::new();
+            for (number, line) in BufReader::new(stream).lines().enumerate() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(err) => fail(
+                        ErrorCode::ConnectionFailed,
+                        &format!("Fehler beim Empfangen vom Daemon: {}", err),
+                    ),
+                };
+                if number == 0 {
+                    exit_if_rejected(&line);
+                }
+
+                if let Some(progress) = line.strip_prefix("progress ") {
+                    say!("{}", progress);
+                    steps.push(progress.to_string());
+                } else if let Some(id) = line.strip_prefix("ok ") {
+                    say!("Archiviert als {}.", id);
+                    output::done(json!({ "id": id, "progress": steps }));
+                    return;
+                } else {
+                    fail(
+                        ErrorCode::ArchiveFailed,
+                        line.strip_prefix("error ").unwrap_or(&line),
+                    );
+                }
+            }
+
+            fail(
+                ErrorCode::ConnectionFailed,
+                "Der Daemon hat die Verbindung geschlossen.",
+            );
+        }
+        Commands::Stats { per } => {
+            let status = daemon_running()
+                .then(|| serde_json::from_str::<Status>(&request_from_daemon("status")).ok())
+                .flatten();
+
+            let result = collect(per, status.as_ref(), |err| {
+                eprintln!("Warnung: Überspringe Thread: {}", err)
+            });
+            match result {
+                Ok(stats) if output::json() => output::done(&stats),
+                Ok(stats) => print_stats(&stats),
+                Err(err) => fail(
+                    ErrorCode::Io,
+                    &format!("Fehler beim Sammeln der Statistiken: {}", err),
+                ),
+            }
+        }
+        Commands::Config { command } => match command {
+            ConfigCommands::Show { effective: true } => print_effective_config(),
+            ConfigCommands::Show { effective: false } => {
+                let config = load_config_or_exit();
+                if output::json() {
+                    output::done(&config);
+                } else {
+                    print!("{}", toml::to_string_pretty(&config).unwrap());
+                }
+            }
+            ConfigCommands::Get { key } => match load_config_or_exit().get(&key) {
+                Ok(value) if output::json() => output::done(json!({ "key": key, "value": value })),
+                Ok(toml::Value::Table(table)) => print!("{}", table),
+                Ok(toml::Value::String(string)) => println!("{}", string),
+                Ok(value) => println!("{}", value),
+                Err(err) => fail(ErrorCode::NotFound, &err),
+            },
+            ConfigCommands::Set { key, value } => {
+                let config = match config::set(&key, &value) {
+                    Ok(config) => config,
+                    Err(err) => fail(ErrorCode::InvalidConfig, &err),
+                };
+                say!("{} gesetzt.", key);
+
+                let running = daemon_running();
+                let restart = if running { reload_daemon() } else { Vec::new() };
+                output::done(json!({
+                    "key": key,
+                    "value": config.get(&key).ok(),
+                    "reloaded": running,
+                    "restart_required": restart,
+                }));
+            }
+            ConfigCommands::Validate => {
+                let (config, sources) = match config::resolve() {
+                    Ok(resolved) => resolved,
+                    Err(err) => fail(ErrorCode::InvalidConfig, &err),
+                };
+                let warnings: Vec<String> = config
+                    .warnings()
+                    .iter()
+                    .chain(&config.rate_warnings())
+                    .map(|warning| config::describe(warning, &sources))
+                    .collect();
+                for warning in &warnings {
+                    say!("Warnung: {}", warning);
+                }
+                say!("Die Konfiguration ist gültig.");
+                output::done(json!({ "valid": true, "warnings": warnings }));
+            }
+        },
+        Commands::Reload => {
+            ensure_daemon_running();
+            let restart = reload_daemon();
+            output::done(json!({ "restart_required": restart }));
+        }
+        Commands::Completions { shell } => completions(shell),
         Commands::Uninstall => {
-            clean();
-            if let Err(err) = remove_file(settings::CLIENT_PATH) {
-                eprintln!("Fehler beim Löschen von {}: {}", settings::CLIENT_PATH, err);
+            let mut exit_code = clean();
+            if let Err(err) = remove_file(&paths().client_binary) {
+                eprintln!(
+                    "Fehler beim Löschen von {}: {}",
+                    &paths().client_binary,
+                    err
+                );
+                exit_code = 1;
             }
-            if let Err(err) = remove_file(settings::DAEMON_PATH) {
-                eprintln!("Fehler beim Löschen von {}: {}", settings::DAEMON_PATH, err);
+            if let Err(err) = remove_file(&paths().daemon_binary) {
+                eprintln!(
+                    "Fehler beim Löschen von {}: {}",
+                    &paths().daemon_binary,
+                    err
+                );
+                exit_code = 1;
             }
+            if exit_code == 0 {
+                output::done(json!({ "uninstalled": true }));
+            } else if output::json() {
+                fail(ErrorCode::Io, "Nicht alle Dateien konnten gelöscht werden.");
+            }
+            exit(exit_code);
+        }
+    }
+}
+
+/// Erstellt das Verzeichnis in dem eine Datei liegt, wenn es noch nicht existiert
+fn create_parent_dir(filepath: &str) {
+    if let Some(dir) = Path::new(filepath).parent() {
+        if let Err(err) = create_dir_all(dir) {
+            fail(
+                ErrorCode::Io,
+                &format!("Fehler beim Erstellen von {:?}: {}", dir, err),
+            );
         }
     }
 }
 
-/// Kopiert eine Datei von from zu to
 fn copy_file(from: &str, to: &str) {
     run_command(Command::new("cp").arg(from).arg(to));
 }
 
 /// Returnt true wenn das Run-Verzeichnis existiert, false wenn nicht
 fn run_dir_exists() -> bool {
-    Path::new(settings::RUN_DIR).exists()
+    Path::new(&paths().run_dir).exists()
 }
 
 /// Erstellt das Verzeichnis in das der Daemon seine Logs und Informationen schreibt, wenn es noch
 /// nicht existiert
 fn create_run_dir() {
     if !run_dir_exists() {
-        if let Err(err) = create_dir(settings::RUN_DIR) {
-            eprintln!("Fehler beim Erstellen von {}: {}", settings::RUN_DIR, err);
-            exit(1);
+        if let Err(err) = create_dir_all(&paths().run_dir) {
+            fail(
+                ErrorCode::Io,
+                &format!("Fehler beim Erstellen von {}: {}", &paths().run_dir, err),
+            );
+        }
+    }
+}
+
+/// Erstellt das Verzeichnis in dem der Daemon Daten speichert die einen Neustart überstehen müssen,
+/// wenn es noch nicht existiert
+fn create_data_dir() {
+    if !Path::new(&paths().data_dir).exists() {
+        if let Err(err) = create_dir_all(&paths().data_dir) {
+            fail(
+                ErrorCode::Io,
+                &format!("Fehler beim Erstellen von {}: {}", &paths().data_dir, err),
+            );
         }
     }
 }
@@ -379,100 +1265,475 @@ fn create_run_dir() {
 fn remove_if_existing(filepath: &str) {
     if Path::new(filepath).exists() {
         if let Err(err) = remove_file(filepath) {
-            eprintln!("Fehler beim Löschen von {}: {}", filepath, err);
-            exit(1);
+            fail(
+                ErrorCode::Io,
+                &format!("Fehler beim Löschen von {}: {}", filepath, err),
+            );
         }
     }
 }
 
 /// Killt den Daemon (unsichere Variante von stop)
 fn kill_daemon() {
-    feddit_archivieren_assert(daemon_running(), "Der Daemon läuft nicht.");
-    feddit_archivieren_assert(root(), "Du bist nicht root.");
+    ensure_daemon_running();
+    ensure(privileged(), ErrorCode::NotRoot, "Du bist nicht root.");
 
-    if !run_dir_exists() {
-        eprintln!("Der Daemon läuft, aber {} existiert nicht, weshalb ich nicht weiß wen ich killen soll.", settings::RUN_DIR);
-        eprintln!("Probiers mal mit dem pkill Befehl?");
-    }
+    ensure(
+        run_dir_exists(),
+        ErrorCode::Io,
+        &format!(
+            "Der Daemon läuft, aber {} existiert nicht, weshalb ich nicht weiß wen ich killen soll.\n\
+             Probiers mal mit dem pkill Befehl?",
+            &paths().run_dir
+        ),
+    );
 
     match Command::new("kill").arg(read_pid_file().unwrap()).output() {
         Ok(output) => {
             if !output.status.success() {
-                eprintln!("Fehler beim Killen des Daemons:");
-                eprintln!("{}", command_output_formater(&output));
-            } else {
-                println!("Daemon erfolgreich gekillt.");
+                fail(
+                    ErrorCode::DaemonUnhealthy,
+                    &format!(
+                        "Fehler beim Killen des Daemons:\n{}",
+                        command_output_formater(&output)
+                    ),
+                );
             }
+            say!("Daemon erfolgreich gekillt.");
         }
-        Err(err) => {
-            eprintln!("Fehler beim Killen des Daemons: {}", err);
-        }
+        Err(err) => fail(
+            ErrorCode::DaemonUnhealthy,
+            &format!("Fehler beim Killen des Daemons: {}", err),
+        ),
     }
 }
 
 fn daemon_ready() -> bool {
-    TcpStream::connect(get(settings::SOCKET_FILE)).is_ok()
+    TcpStream::connect(get(&paths().socket_file())).is_ok()
 }
 
 /// Öffnet einen TcpStream mit dem Daemon und schreibt eine Nachricht hinein.
 /// Returnt am Ende den erstellten TcpStream.
 fn send_to_daemon(message: &str) -> TcpStream {
     // Den Stream erstellen
-    // Das Ziel ist die Adresse die der Daemon ins SOCKET_FILE geschrieben hat
-    let mut stream = match TcpStream::connect(get(settings::SOCKET_FILE)) {
+    // Das Ziel ist die Adresse die der Daemon ins Socketfile geschrieben hat
+    let mut stream = match TcpStream::connect(get(&paths().socket_file())) {
         Ok(stream) => stream,
-        Err(err) => {
-            eprintln!(
+        Err(err) => fail(
+            ErrorCode::ConnectionFailed,
+            &format!(
                 "Fehler beim Verbinden mit {} ({}): {}",
-                get(settings::SOCKET_FILE),
-                settings::SOCKET_FILE,
+                get(&paths().socket_file()),
+                &paths().socket_file(),
                 err
-            );
-            exit(1);
-        }
+            ),
+        ),
     };
 
     // Die Nachricht in den Stream schreiben
     if let Err(err) = stream.write_all(message.as_bytes()) {
-        eprintln!("Fehler beim Senden an den Daemon: {}", err);
-        exit(1);
+        fail(
+            ErrorCode::ConnectionFailed,
+            &format!("Fehler beim Senden an den Daemon: {}", err),
+        );
     }
 
     // Den Stream returnen
     stream
 }
 
+/// Lässt den Daemon suchen. Er schickt jeden Treffer als eigene Zeile, die letzte Zeile ist das
+/// Ergebnis.
+fn search_with_daemon(query: &SearchQuery) -> Result<Vec<SearchHit>, String> {
+    let json = serde_json::to_string(query).unwrap();
+    let stream = send_to_daemon(&format!("search {}", json));
+
+    let mut hits = Vec::new();
+    for (number, line) in BufReader::new(stream).lines().enumerate() {
+        let line = line.map_err(|err| format!("Fehler beim Empfangen vom Daemon: {}", err))?;
+        if number == 0 {
+            exit_if_rejected(&line);
+        }
+
+        if let Some(hit) = line.strip_prefix("hit ") {
+            hits.push(serde_json::from_str(hit).map_err(|err| {
+                format!("Der Daemon hat eine ungültige Antwort gesendet: {}", err)
+            })?);
+        } else if let Some(warning) = line.strip_prefix("warning ") {
+            eprintln!("Warnung: Überspringe Thread: {}", warning);
+        } else if line == "ok" {
+            return Ok(hits);
+        } else {
+            return Err(line.strip_prefix("error ").unwrap_or(&line).to_string());
+        }
+    }
+
+    Err("Der Daemon hat die Verbindung geschlossen.".to_string())
+}
+
+/// Sendet eine Nachricht an den Daemon und returnt seine vollständige Antwort
+fn request_from_daemon(message: &str) -> String {
+    let mut stream = send_to_daemon(message);
+    let mut response = String::new();
+    if let Err(err) = stream.read_to_string(&mut response) {
+        fail(
+            ErrorCode::ConnectionFailed,
+            &format!("Fehler beim Empfangen vom Daemon: {}", err),
+        );
+    }
+    exit_if_rejected(&response);
+    response
+}
+
+/// Exitet mit einer Fehlermeldung wenn der Daemon die Anfrage abgelehnt hat, z.B. weil er zu viele
+/// Verbindungen hat
+fn exit_if_rejected(response: &str) {
+    if let Some(reason) = response.strip_prefix("rejected ") {
+        fail(
+            ErrorCode::Rejected,
+            &format!(
+                "Der Daemon hat die Anfrage abgelehnt: {}",
+                reason.trim_end()
+            ),
+        );
+    }
+}
+
+/// Printet für das Standardprofil und jedes weitere Profil ob sein Daemon läuft
+fn print_all_profiles() {
+    let mut infos = Vec::new();
+    for profile in [None]
+        .into_iter()
+        .chain(config::profiles().into_iter().map(Some))
+    {
+        // Für das eigene Profil gelten auch Umgebungsvariablen und --set
+        let paths = if profile == config::profile() {
+            paths().clone()
+        } else {
+            config::paths_of(profile.as_deref()).unwrap_or_else(|err| {
+                fail(
+                    ErrorCode::InvalidConfig,
+                    &format!(
+                        "Fehler beim Lesen der Konfiguration von Profil {}: {}",
+                        profile.as_deref().unwrap_or("default"),
+                        err
+                    ),
+                )
+            })
+        };
+
+        let running = daemon_running_at(&paths);
+        infos.push(json!({
+            "profile": profile,
+            "config": config::config_file_of(profile.as_deref()),
+            "running": running,
+            "address": running.then(|| get(&paths.socket_file())),
+            "pid": running.then(|| get(&paths.pid_file()).trim().parse::<u32>().ok()).flatten(),
+        }));
+    }
+
+    if output::json() {
+        output::done(json!({ "profiles": infos }));
+        return;
+    }
+
+    for info in infos {
+        let name = info["profile"].as_str().unwrap_or("default");
+        if info["running"].as_bool().unwrap_or_default() {
+            println!(
+                "{}:\tläuft (PID {}, Port {})",
+                name,
+                info["pid"],
+                info["address"].as_str().unwrap_or_default()
+            );
+        } else {
+            println!("{}:\tläuft nicht", name);
+        }
+    }
+}
+
+/// Lässt den Daemon die Konfiguration neu laden und returnt die Schlüssel, die erst nach einem
+/// Neustart wirken
+fn reload_daemon() -> Vec<String> {
+    let response = request_from_daemon("reload");
+    let restart = match response.strip_prefix("ok") {
+        Some(rest) => rest.trim().strip_prefix("restart").unwrap_or_default(),
+        None => fail(
+            ErrorCode::InvalidConfig,
+            &format!(
+                "Fehler beim Neuladen der Konfiguration: {}",
+                response.strip_prefix("error ").unwrap_or(&response)
+            ),
+        ),
+    };
+    let restart: Vec<String> = restart
+        .split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    say!("Der Daemon hat die Konfiguration neu geladen.");
+    if !restart.is_empty() {
+        say!("Erst nach einem Neustart wirksam: {}", restart.join(", "));
+    }
+    restart
+}
+
+fn ensure_daemon_running() {
+    ensure(
+        daemon_running(),
+        ErrorCode::DaemonNotRunning,
+        "Der Daemon läuft nicht.",
+    );
+}
+
+/// Hängt den Bereich für `pause` und `resume` an den Befehl an
+fn scoped_message(command: &str, instance: Option<String>, community: Option<String>) -> String {
+    match (instance, community) {
+        (Some(instance), _) => format!("{} instance={}", command, instance),
+        (_, Some(community)) => format!("{} community={}", command, community),
+        (None, None) => command.to_string(),
+    }
+}
+
+/// Der Bereich aus einer Nachricht von `scoped_message`, `None` wenn alles gemeint ist
+fn scope_of(message: &str) -> Option<&str> {
+    message.split_once(' ').map(|(_, scope)| scope)
+}
+
+/// Für `config::current`, damit auch dort ein Fehler mit `--json` als JSON ausgegeben wird
+fn invalid_config(err: &str) -> ! {
+    fail(ErrorCode::InvalidConfig, err)
+}
+
+/// Lädt die Konfiguration, exitet mit einer Fehlermeldung wenn sie ungültig ist
+fn load_config_or_exit() -> Config {
+    match Config::load() {
+        Ok(config) => config,
+        Err(err) => fail(ErrorCode::InvalidConfig, &err),
+    }
+}
+
+/// Printet jeden Wert der aufgelösten Konfiguration mit seiner Herkunft
+fn print_effective_config() {
+    let (config, sources) = match config::resolve() {
+        Ok(resolved) => resolved,
+        Err(err) => fail(ErrorCode::InvalidConfig, &err),
+    };
+    let table = toml::Table::try_from(&config).unwrap();
+
+    let mut values = Vec::new();
+    for (key, value) in config::leaves(&table) {
+        let source = config::source_of(&sources, &key);
+
+        say!("{} = {}\t({})", key, value, source);
+        let mut entry = json!({ "key": key, "value": value, "source": source.name() });
+        if let config::Source::Env(var) = &source {
+            entry["variable"] = json!(var);
+        }
+        values.push(entry);
+    }
+    output::done(json!({ "values": values }));
+}
+
+/// Parst einen Zeitpunkt, exitet mit einer Fehlermeldung wenn das nicht geht
+fn parse_time_or_exit(input: &str) -> DateTime<Local> {
+    match parse_time(input) {
+        Ok(time) => time,
+        Err(err) => fail(ErrorCode::InvalidArgument, &err),
+    }
+}
+
+/// Printet die Treffer einer Suche
+fn print_search_hits(hits: &[SearchHit]) {
+    if hits.is_empty() {
+        println!("Keine Treffer.");
+        return;
+    }
+
+    for hit in hits {
+        let location = match hit.comment_id {
+            Some(comment) => format!("{}/{}#{}", hit.instance, hit.post_id, comment),
+            None => format!("{}/{}", hit.instance, hit.post_id),
+        };
+        println!("{} [{}] {}", location, hit.score, hit.title);
+        println!(
+            "\t{} von {}, {}",
+            hit.community,
+            hit.author,
+            hit.published.with_timezone(&Local).format("%d.%m.%Y %H:%M")
+        );
+        if !hit.excerpt.is_empty() {
+            println!("\t{}", hit.excerpt);
+        }
+    }
+
+    println!("{} Treffer.", hits.len());
+}
+
+/// Printet den Status des Daemons
+fn print_status(status: &Status) {
+    let time_format = "%d.%m.%Y %H:%M:%S";
+
+    println!("Version:\t\t{}", status.version);
+    println!(
+        "Gestartet:\t\t{} (vor {})",
+        status.started.format(time_format),
+        format_duration(status.uptime)
+    );
+    if status.paused.is_empty() {
+        println!("Pausiert:\t\tnichts");
+    } else {
+        println!("Pausiert:\t\t{}", status.paused.join(", "));
+    }
+    println!("Warteschlange:\t\t{} Posts", status.queue);
+    println!(
+        "Archiviert:\t\t{} Posts ({} Fehler)",
+        status.archived, status.archive_errors
+    );
+    match &status.last_update_check {
+        None => println!("Updateüberprüfung:\tnoch keine"),
+        Some(check) => match &check.error {
+            None => println!(
+                "Updateüberprüfung:\t{} (erfolgreich)",
+                check.time.format(time_format)
+            ),
+            Some(err) => println!(
+                "Updateüberprüfung:\t{} (Fehler: {})",
+                check.time.format(time_format),
+                err
+            ),
+        },
+    }
+
+    for target in &status.targets {
+        println!();
+        println!("Ziel:\t\t\t{}", target.url);
+        println!("Seite:\t\t\t{}", target.page);
+        match (target.last_post, target.last_fetch) {
+            (Some(post), Some(time)) => {
+                println!("Letzter Post:\t\t{} ({})", post, time.format(time_format))
+            }
+            _ => println!("Letzter Post:\t\tnoch keiner"),
+        }
+        println!("Fehler:\t\t\t{}", target.errors);
+    }
+}
+
+/// Formatiert eine Anzahl an Sekunden als z.B. `1d 2h 3m 4s`
+fn format_duration(seconds: i64) -> String {
+    let (days, rest) = (seconds / 86400, seconds % 86400);
+    let (hours, rest) = (rest / 3600, rest % 3600);
+    let (minutes, seconds) = (rest / 60, rest % 60);
+
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{}s", seconds),
+        (0, 0, _) => format!("{}m {}s", minutes, seconds),
+        (0, _, _) => format!("{}h {}m {}s", hours, minutes, seconds),
+        _ => format!("{}d {}h {}m {}s", days, hours, minutes, seconds),
+    }
+}
+
 fn start_daemon() {
-    // Das Run-Verzeichnis für den Daemon erstellen
+    if let Err(err) = try_start_daemon() {
+        fail(ErrorCode::DaemonUnhealthy, &err);
+    }
+}
+
+/// Wie `start_daemon`, aber Fehler werden zurückgegeben statt zu exiten
+fn try_start_daemon() -> Result<(), String> {
+    // Das Run und Daten-Verzeichnis für den Daemon erstellen
     create_run_dir();
+    create_data_dir();
 
     // Den Daemon launchen
-    match Command::new(settings::DAEMON_PATH).output() {
+    match Command::new(&paths().daemon_binary).output() {
         Ok(output) => {
             if !output.status.success() {
-                println!("Fehler beim Starten des Daemons:");
-                println!("{}", command_output_formater(&output));
-                exit(1);
+                return Err(format!(
+                    "Fehler beim Starten des Daemons:\n{}",
+                    command_output_formater(&output)
+                ));
             }
 
-            if wait_with_timeout!(|| daemon_running(), Duration::from_secs(1)) {
-                println!("Daemon erfolgreich gestartet!");
+            if wait_with_timeout!(daemon_running, Duration::from_secs(1)) {
+                say!("Daemon erfolgreich gestartet!");
+                Ok(())
             } else {
-                println!("Der Daemon ist nicht online gegangen.");
+                Err("Der Daemon ist nicht online gegangen.".to_string())
             }
         }
-        Err(err) => {
-            println!("Fehler beim Starten des Daemons: {}", err);
-            exit(1);
+        Err(err) => Err(format!("Fehler beim Starten des Daemons: {}", err)),
+    }
+}
+
+/// Beobachtet den Daemon `grace` lang, er darf in der Zeit nicht abstürzen und muss danach noch
+/// auf `ping` antworten
+fn watch_health(grace: Duration) -> Result<(), String> {
+    say!(
+        "Überprüfe {} Sekunden lang ob der Daemon gesund bleibt...",
+        grace.as_secs()
+    );
+    let start = Instant::now();
+    while start.elapsed() < grace {
+        if !daemon_running() {
+            return Err("Der Daemon ist abgestürzt.".to_string());
         }
+        thread::sleep(Duration::from_secs(1));
+    }
+
+    let mut stream = TcpStream::connect(get(&paths().socket_file()))
+        .map_err(|err| format!("Fehler beim Verbinden mit dem Daemon: {}", err))?;
+    stream
+        .write_all(b"ping")
+        .map_err(|err| format!("Fehler beim Senden an den Daemon: {}", err))?;
+    match read_from_stream(&mut stream) {
+        message if message == "pong" => Ok(()),
+        message => Err(format!("Nachricht pong erwartet, '{}' empfangen.", message)),
     }
 }
 
-/// Funktion die vom Feddit-Thread ausgeführt wird
-#[allow(dead_code)]
-fn feddit() {}
+/// Rollt auf `previous` zurück, nachdem die gerade installierte Version `failed` nach dem Neustart
+/// nicht gesund war. Die kaputte Version kommt nach `update.skip`, damit der Daemon sie nicht gleich
+/// wieder installiert.
+fn roll_back_failed_install(failed: Version, previous: Option<Version>, err: &str) -> Version {
+    say_err!("Die neue Version {} ist nicht gesund: {}", failed, err);
+
+    let retained = versions::list()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|retained| {
+            previous.as_ref() == Some(&retained.version) && retained.version != failed
+        });
+    let Some(retained) = retained else {
+        fail(
+            ErrorCode::DaemonUnhealthy,
+            "Es gibt keine ältere Version auf die zurückgerollt werden kann.",
+        );
+    };
+
+    say_err!("Rolle auf Version {} zurück...", retained.version);
+    if daemon_running() {
+        kill_daemon();
+    }
+    if let Err(err) = versions::restore(&retained) {
+        fail(ErrorCode::InstallFailed, &err);
+    }
+    if root() {
+        chmod(&paths().daemon_binary, "777");
+        chmod(&paths().client_binary, "777");
+    }
+    match config::skip_version(&failed) {
+        Ok(_) => say_err!("Version {} steht jetzt in update.skip.", failed),
+        Err(err) => say_err!("Fehler beim Eintragen in update.skip: {}", err),
+    }
+
+    start_daemon();
+    retained.version
+}
 
-/// Löscht RUN_DIR und UPDATE_DIR
+/// Löscht `run_dir` und `update_dir`
 fn clean() -> i32 {
     let mut exit_code = 0;
 
@@ -480,19 +1741,15 @@ fn clean() -> i32 {
         kill_daemon();
     }
 
-    if Path::new(settings::RUN_DIR).exists() {
-        if let Err(error) = remove_dir_all(settings::RUN_DIR) {
-            eprintln!("Fehler beim Löschen von {}: {}", settings::RUN_DIR, error);
+    if Path::new(&paths().run_dir).exists() {
+        if let Err(error) = remove_dir_all(&paths().run_dir) {
+            eprintln!("Fehler beim Löschen von {}: {}", &paths().run_dir, error);
             exit_code = 1;
         }
     }
-    if Path::new(settings::UDPATE_DIR).exists() {
-        if let Err(error) = remove_dir_all(settings::UDPATE_DIR) {
-            eprintln!(
-                "Fehler beim Löschen von {}: {}",
-                settings::UDPATE_DIR,
-                error
-            );
+    if Path::new(&paths().update_dir).exists() {
+        if let Err(error) = remove_dir_all(&paths().update_dir) {
+            eprintln!("Fehler beim Löschen von {}: {}", &paths().update_dir, error);
             exit_code = 1;
         }
     }
@@ -503,7 +1760,7 @@ fn clean() -> i32 {
 fn stop_daemon() -> Result<(), String> {
     let mut stream = send_to_daemon("stop");
     let response = read_from_stream(&mut stream);
-    if !(response == "ok") {
+    if response != "ok" {
         return Err(format!(
             "Der Daemon hat eine unerwartete Antwort gesendet: {}",
             response
@@ -511,7 +1768,7 @@ fn stop_daemon() -> Result<(), String> {
     }
 
     // Darauf warten, dass der Daemon exitet, maximal 1 Sekunde lang warten
-    let daemon_stopped = wait_with_timeout!(daemon_running, Duration::from_secs(1));
+    let daemon_stopped = wait_with_timeout!(|| !daemon_running(), Duration::from_secs(1));
 
     if !daemon_stopped {
         Err("Der Daemon hat eine Bestätigung gesendet, läuft aber immer noch.".to_string())
@@ -522,23 +1779,18 @@ fn stop_daemon() -> Result<(), String> {
 
 /// Restartet den Daemon
 fn restart_daemon() -> Result<(), String> {
-    println!("Sende...");
     let mut stream = send_to_daemon("restart");
-    println!("Empfange...");
     let response = read_from_stream(&mut stream);
-    if !(response == "ok") {
+    if response != "ok" {
         return Err(format!(
             "Der Daemon hat eine unerwartete Antwort gesendet: {}",
             response
         ));
     }
-    println!("Empfangen erfolgreich");
 
     // Darauf warten, dass der Daemon exitet, maximal 1 Sekunde lang warten
     let daemon_stopped = wait_with_timeout!(|| !daemon_running(), Duration::from_secs(1));
 
-    println!("Der Daemon wurde gestoppt");
-
     if !daemon_stopped {
         Err("Der Daemon hat eine Bestätigung gesendet, läuft aber immer noch.".to_string())
     } else {
@@ -547,6 +1799,7 @@ fn restart_daemon() -> Result<(), String> {
 }
 
 /// Vom Deamonize Source Code "inspiriert"
+#[allow(clippy::macro_metavars_in_unsafe)]
 fn set_stdio_to_file(filename: &str) -> Result<(), String> {
     let file = match File::create(filename) {
         Ok(file) => file,
@@ -0,0 +1,79 @@
+use std::io::{stdout, Write};
+
+use clap_complete::{env::Shells, CompletionCandidate, Shell};
+
+use serde_json::json;
+
+use crate::{
+    archive,
+    output::{self, fail, ErrorCode},
+};
+
+/// Die Umgebungsvariable mit der die Shell das Programm zum Vervollständigen aufruft
+pub const COMPLETE_VAR: &str = "COMPLETE";
+const BIN_NAME: &str = "feddit_archivieren";
+
+/// Printet das Skript mit dem sich die Vervollständigung in der Shell registriert. Die eigentliche
+/// Vervollständigung macht das Skript dann, indem es das Programm mit COMPLETE_VAR aufruft, damit
+/// sie immer zur installierten Version und zum aktuellen Archiv passt. Mit `--json` steht das
+/// Skript in `script`.
+pub fn completions(shell: Shell) {
+    let shells = Shells::builtins();
+    let Some(completer) = shells.completer(&shell.to_string()) else {
+        fail(
+            ErrorCode::InvalidArgument,
+            &format!("Für {} gibt es keine Vervollständigung.", shell),
+        );
+    };
+
+    let mut script = Vec::new();
+    let written = completer
+        .write_registration(COMPLETE_VAR, BIN_NAME, BIN_NAME, BIN_NAME, &mut script)
+        .and_then(|_| {
+            if output::json() {
+                Ok(())
+            } else {
+                stdout().write_all(&script)
+            }
+        });
+    if let Err(err) = written {
+        fail(ErrorCode::Io, &format!("Fehler beim Schreiben: {}", err));
+    }
+
+    output::done(json!({
+        "shell": shell.to_string(),
+        "script": String::from_utf8_lossy(&script),
+    }));
+}
+
+/// Alle Instanzen im Archiv
+pub fn instances() -> Vec<CompletionCandidate> {
+    archive::instances()
+        .unwrap_or_default()
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Alle Communities im Archiv
+pub fn communities() -> Vec<CompletionCandidate> {
+    archive::communities()
+        .unwrap_or_default()
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Alle archivierten Posts als `<instanz>/<id>`, so wie `show` sie erwartet
+pub fn posts() -> Vec<CompletionCandidate> {
+    archive::instances()
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|instance| {
+            archive::post_ids(&instance)
+                .unwrap_or_default()
+                .into_iter()
+                .map(move |id| CompletionCandidate::new(format!("{}/{}", instance, id)))
+        })
+        .collect()
+}
@@ -0,0 +1,1400 @@
+#![allow(dead_code)]
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env,
+    ffi::CString,
+    fmt,
+    fs::{create_dir_all, read_dir, read_to_string, write},
+    io::ErrorKind,
+    os::unix::ffi::OsStrExt,
+    path::Path,
+    process::exit,
+    sync::{Once, OnceLock},
+};
+
+use semver::Version;
+use serde::{
+    de::{self, Unexpected, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use toml_edit::{DocumentMut, ImDocument, Item, Key, TableLike};
+
+use crate::{
+    helpers::{instance_of, parse_duration, privileged},
+    lemmy::Lemmy,
+    log::Level,
+    rules::{Rules, RulesConfig},
+    settings::{self, CONFIG_FILE, ENV_PREFIX, OVERRIDES_VAR, PROFILE_VAR, USER_MODE_VAR},
+    trust_me_bro,
+};
+
+/// Die Konfiguration mit der das Programm gestartet wurde, siehe `current`
+static CURRENT: OnceLock<Config> = OnceLock::new();
+/// Was `current` mit dem Fehler einer ungültigen Konfiguration macht, siehe `on_invalid`
+static ON_INVALID: OnceLock<fn(&str) -> !> = OnceLock::new();
+/// Unbekannte Umgebungsvariablen werden nur beim ersten Laden gemeldet, sie ändern sich nicht
+static UNKNOWN_VARS_WARNED: Once = Once::new();
+
+/// Die Konfiguration von Daemon und Client, gelesen aus `config_file()`. Alles was dort fehlt
+/// bekommt den Standardwert aus settings.rs, im Benutzermodus die Pfade aus den XDG-Verzeichnissen.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub paths: PathsConfig,
+    pub crawl: CrawlConfig,
+    pub update: UpdateConfig,
+    pub control: ControlConfig,
+    pub log: LogConfig,
+}
+
+/// Wo die Dateien des Programms liegen. Die einzelnen Dateien werden aus den Verzeichnissen
+/// abgeleitet, z.B. liegt das PID-File immer in `run_dir`. Jedes Profil hat standardmäßig ein
+/// eigenes `run_dir` und `data_dir` (z.B. `/run/feddit_archivieren@test`), die binarys und das
+/// Update teilen sich alle.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    /// Für Dateien die nur so lange leben wie der Daemon (PID, Socket, Logs)
+    pub run_dir: String,
+    /// Für Dateien die einen Neustart überstehen müssen (Archiv, Medien, Pausen)
+    pub data_dir: String,
+    /// Hierhin wird das Repository für Updates geklont
+    pub update_dir: String,
+    /// Hier wird der Build-Cache zwischen zwei Updates aufgehoben
+    pub update_cache_dir: String,
+    /// Wohin die binarys installiert werden
+    pub daemon_binary: String,
+    pub client_binary: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CrawlConfig {
+    /// Die URLs die gecrawlt werden
+    pub targets: Vec<String>,
+    /// Wie viele Anfragen der Daemon höchstens pro Minute an eine Instanz schickt
+    pub requests_per_minute: u32,
+    /// Größere Bilder und Videos (in Bytes) werden nicht heruntergeladen
+    pub max_media_size: u64,
+    pub rules: RulesConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpdateConfig {
+    /// Sekunden zwischen zwei Updateüberprüfungen
+    #[serde(deserialize_with = "deserialize_seconds")]
+    pub interval: u64,
+    /// Das Git-Repository aus dem Updates geholt werden, eine URL oder ein lokaler Pfad
+    pub repository: String,
+    /// Der Kanal dem die Installation folgt, muss in `channels` stehen
+    pub channel: String,
+    /// Woher jeder Kanal seine Versionen bekommt. Wird die Tabelle in der Datei angegeben, ersetzt
+    /// sie die Standardkanäle komplett.
+    pub channels: BTreeMap<String, ChannelConfig>,
+    /// Bleibt auf dieser Version statt dem Kanal zu folgen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pin: Option<Version>,
+    /// Versionen die nie installiert werden, z.B. weil sie bekannt kaputt sind
+    pub skip: Vec<Version>,
+    /// Wie viele vorher installierte Versionen für `rollback` aufgehoben werden, 0 schaltet das ab
+    pub keep_versions: usize,
+    /// Sekunden die der Daemon nach einem Update gesund bleiben muss, sonst wird zurückgerollt
+    #[serde(deserialize_with = "deserialize_seconds")]
+    pub health_grace: u64,
+}
+
+/// Ein Updatekanal folgt entweder einem Branch oder dem neusten Tag der auf ein Muster passt
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ChannelConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Ein Glob-Muster wie `v*`, es wird der Tag mit der höchsten Version genommen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ControlConfig {
+    /// Wie viele Ausgaben sich der Daemon für neue Listener merkt
+    pub backlog_size: usize,
+    /// Sekunden die eine Verbindung still sein darf
+    #[serde(deserialize_with = "deserialize_seconds")]
+    pub connection_timeout: u64,
+    /// Sekunden die eine Session zwischen zwei Befehlen still sein darf
+    #[serde(deserialize_with = "deserialize_seconds")]
+    pub session_timeout: u64,
+    pub max_connections: usize,
+    pub max_listeners: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// Einträge unter diesem Level landen nicht in den Logdateien, wirkt auch beim Neuladen
+    pub level: Level,
+    /// Wie viele alte Versionen der Logs beim Start des Daemons behalten werden
+    pub rotations: usize,
+}
+
+/// Sekunden, entweder als Zahl oder als Zeitangabe wie `2h` (siehe `parse_duration`)
+fn deserialize_seconds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    struct Seconds;
+
+    impl Visitor<'_> for Seconds {
+        type Value = u64;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("Sekunden oder eine Zeitangabe wie \"2h\"")
+        }
+
+        fn visit_u64<E: de::Error>(self, seconds: u64) -> Result<u64, E> {
+            Ok(seconds)
+        }
+
+        fn visit_i64<E: de::Error>(self, seconds: i64) -> Result<u64, E> {
+            u64::try_from(seconds).map_err(|_| E::invalid_value(Unexpected::Signed(seconds), &self))
+        }
+
+        fn visit_str<E: de::Error>(self, duration: &str) -> Result<u64, E> {
+            parse_duration(duration)
+                .map(|duration| duration.as_secs())
+                .map_err(E::custom)
+        }
+    }
+
+    deserializer.deserialize_any(Seconds)
+}
+
+impl PathsConfig {
+    /// Die Standardpfade für ein Profil, `None` ist das Standardprofil
+    fn defaults(profile: Option<&str>) -> PathsConfig {
+        let mut paths = if user_mode() {
+            PathsConfig::user()
+        } else {
+            PathsConfig {
+                run_dir: settings::RUN_DIR.to_string(),
+                data_dir: settings::DATA_DIR.to_string(),
+                update_dir: settings::UDPATE_DIR.to_string(),
+                update_cache_dir: settings::UDPATE_CACHE_DIR.to_string(),
+                daemon_binary: settings::DAEMON_PATH.to_string(),
+                client_binary: settings::CLIENT_PATH.to_string(),
+            }
+        };
+
+        if let Some(profile) = profile {
+            paths.run_dir = format!("{}@{}", paths.run_dir, profile);
+            paths.data_dir = format!("{}@{}", paths.data_dir, profile);
+        }
+        paths
+    }
+
+    /// Die Pfade im Benutzermodus, alles liegt im Home-Verzeichnis oder in `$XDG_RUNTIME_DIR`
+    fn user() -> PathsConfig {
+        let bin = PathsConfig::file(&home(), ".local/bin");
+        let cache = xdg_dir("XDG_CACHE_HOME", ".cache");
+
+        PathsConfig {
+            run_dir: PathsConfig::file(&runtime_dir(), "feddit_archivieren"),
+            data_dir: PathsConfig::file(
+                &xdg_dir("XDG_DATA_HOME", ".local/share"),
+                "feddit_archivieren",
+            ),
+            update_dir: PathsConfig::file(&cache, "feddit_archivieren/update"),
+            update_cache_dir: PathsConfig::file(&cache, "feddit_archivieren/update_cache"),
+            daemon_binary: PathsConfig::file(&bin, "feddit_archive_daemon"),
+            client_binary: PathsConfig::file(&bin, "feddit_archivieren"),
+        }
+    }
+
+    fn file(dir: &str, name: &str) -> String {
+        format!("{}/{}", dir.trim_end_matches('/'), name)
+    }
+
+    pub fn pid_file(&self) -> String {
+        PathsConfig::file(&self.run_dir, "daemon.pid")
+    }
+
+    /// Die Warnungen und Fehler des Daemons
+    pub fn err_file(&self) -> String {
+        PathsConfig::file(&self.run_dir, "daemon.err")
+    }
+
+    /// Die normalen Ausgaben des Daemons
+    pub fn out_file(&self) -> String {
+        PathsConfig::file(&self.run_dir, "daemon.out")
+    }
+
+    pub fn url_file(&self) -> String {
+        PathsConfig::file(&self.run_dir, "url.txt")
+    }
+
+    pub fn update_log_file(&self) -> String {
+        PathsConfig::file(&self.run_dir, "update_log.txt")
+    }
+
+    pub fn post_file(&self) -> String {
+        PathsConfig::file(&self.run_dir, "posts.txt")
+    }
+
+    /// Enthält die Adresse an der der Daemon auf Befehle hört
+    pub fn socket_file(&self) -> String {
+        PathsConfig::file(&self.run_dir, "daemon.sck")
+    }
+
+    pub fn pause_file(&self) -> String {
+        PathsConfig::file(&self.data_dir, "paused.txt")
+    }
+
+    pub fn archive_dir(&self) -> String {
+        PathsConfig::file(&self.data_dir, "archive")
+    }
+
+    pub fn media_dir(&self) -> String {
+        PathsConfig::file(&self.data_dir, "media")
+    }
+
+    /// Enthält die vorher installierten Versionen, eine pro Unterverzeichnis
+    pub fn versions_dir(&self) -> String {
+        PathsConfig::file(&self.data_dir, "versions")
+    }
+}
+
+impl Default for PathsConfig {
+    fn default() -> Self {
+        PathsConfig::defaults(profile().as_deref())
+    }
+}
+
+impl Default for CrawlConfig {
+    fn default() -> Self {
+        CrawlConfig {
+            targets: vec![settings::FEDDIT_LINK.to_string()],
+            requests_per_minute: settings::REQUESTS_PER_MINUTE,
+            max_media_size: settings::MAX_MEDIA_SIZE,
+            rules: RulesConfig::default(),
+        }
+    }
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        UpdateConfig {
+            interval: settings::UPDATE_FETCH_DELAY.as_secs(),
+            repository: settings::GITHUB_LINK.to_string(),
+            channel: settings::UPDATE_CHANNEL.to_string(),
+            channels: BTreeMap::from([
+                ("stable".to_string(), ChannelConfig::branch("main")),
+                ("dev".to_string(), ChannelConfig::branch("dev")),
+            ]),
+            pin: None,
+            skip: Vec::new(),
+            keep_versions: settings::KEEP_VERSIONS,
+            health_grace: settings::HEALTH_GRACE.as_secs(),
+        }
+    }
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        ControlConfig {
+            backlog_size: settings::BACKLOG_SIZE,
+            connection_timeout: settings::CONNECTION_TIMEOUT.as_secs(),
+            session_timeout: settings::SESSION_TIMEOUT.as_secs(),
+            max_connections: settings::MAX_CONNECTIONS,
+            max_listeners: settings::MAX_LISTENERS,
+        }
+    }
+}
+
+impl ChannelConfig {
+    fn branch(branch: &str) -> ChannelConfig {
+        ChannelConfig {
+            branch: Some(branch.to_string()),
+            tag: None,
+        }
+    }
+}
+
+impl fmt::Display for ChannelConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.branch, &self.tag) {
+            (Some(branch), _) => write!(f, "Branch {}", branch),
+            (None, Some(tag)) => write!(f, "Tag {}", tag),
+            (None, None) => write!(f, "nichts"),
+        }
+    }
+}
+
+impl UpdateConfig {
+    /// Der Kanal dem die Installation gerade folgt
+    pub fn current_channel(&self) -> Result<&ChannelConfig, String> {
+        self.channels.get(&self.channel).ok_or_else(|| {
+            format!(
+                "Unbekannter Updatekanal \"{}\", bekannt sind: {}",
+                self.channel,
+                self.channels.keys().cloned().collect::<Vec<_>>().join(", ")
+            )
+        })
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: Level::Info,
+            rotations: settings::LOG_ROTATIONS,
+        }
+    }
+}
+
+impl Config {
+    /// Lädt und überprüft die Konfiguration, existiert die Datei nicht werden die Standardwerte
+    /// verwendet
+    pub fn load() -> Result<Config, String> {
+        resolve().map(|(config, _)| config)
+    }
+
+    /// Liest die Konfigurationsdatei, Fehler enthalten Zeile und Schlüssel
+    pub fn parse(content: &str) -> Result<Config, String> {
+        toml::from_str(content).map_err(|err| {
+            let span = err.span().unwrap_or_default();
+            let key = key_at(content, span.start).unwrap_or_default();
+            format!(
+                "{}:{}: {}{}",
+                config_file(),
+                line(content, span.start),
+                if key.is_empty() {
+                    String::new()
+                } else {
+                    format!("{}: ", key)
+                },
+                err.message().trim()
+            )
+        })
+    }
+
+    /// Überprüft die Werte, die sich nicht schon über die Typen prüfen lassen
+    pub fn validate(&self) -> Result<(), String> {
+        let errors = self.errors();
+        if errors.is_empty() {
+            return Ok(());
+        }
+        Err(errors
+            .iter()
+            .map(Issue::to_string)
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    /// Alle Fehler wegen denen die Konfiguration nicht verwendet werden kann
+    pub fn errors(&self) -> Vec<Issue> {
+        let mut errors = Vec::new();
+
+        for (key, path, directory) in [
+            ("paths.run_dir", &self.paths.run_dir, true),
+            ("paths.data_dir", &self.paths.data_dir, true),
+            ("paths.update_dir", &self.paths.update_dir, true),
+            ("paths.update_cache_dir", &self.paths.update_cache_dir, true),
+            ("paths.daemon_binary", &self.paths.daemon_binary, false),
+            ("paths.client_binary", &self.paths.client_binary, false),
+        ] {
+            if !Path::new(path).is_absolute() {
+                errors.push(Issue::new(
+                    key,
+                    format!("\"{}\" ist kein absoluter Pfad.", path),
+                ));
+            } else if let Err(err) = reachable(path, directory) {
+                errors.push(Issue::new(key, err));
+            }
+        }
+
+        if self.crawl.targets.is_empty() {
+            errors.push(Issue::new(
+                "crawl.targets",
+                "Es muss mindestens ein Ziel angegeben werden.",
+            ));
+        }
+        for target in &self.crawl.targets {
+            match reqwest::Url::parse(target) {
+                Ok(url) if ["http", "https"].contains(&url.scheme()) && url.has_host() => {}
+                Ok(_) => errors.push(Issue::new(
+                    "crawl.targets",
+                    format!("\"{}\" ist keine http(s)-URL.", target),
+                )),
+                Err(err) => errors.push(Issue::new(
+                    "crawl.targets",
+                    format!("Ungültige URL \"{}\": {}", target, err),
+                )),
+            }
+        }
+
+        if let Err(err) = Rules::new(&self.crawl.rules) {
+            errors.push(Issue::new("crawl.rules.deny_patterns", err));
+        }
+
+        if !Path::new(&self.update.repository).is_absolute() {
+            if let Err(err) = reqwest::Url::parse(&self.update.repository) {
+                errors.push(Issue::new(
+                    "update.repository",
+                    format!(
+                        "\"{}\" ist weder eine URL noch ein absoluter Pfad: {}",
+                        self.update.repository, err
+                    ),
+                ));
+            }
+        }
+        if let Err(err) = self.update.current_channel() {
+            errors.push(Issue::new("update.channel", err));
+        }
+        if let Some(pin) = &self.update.pin {
+            if self.update.skip.contains(pin) {
+                errors.push(Issue::new(
+                    "update.pin",
+                    format!("{} steht auch in update.skip.", pin),
+                ));
+            }
+        }
+        for (name, channel) in &self.update.channels {
+            match (&channel.branch, &channel.tag) {
+                (Some(value), None) | (None, Some(value)) if !value.is_empty() => {}
+                _ => errors.push(Issue::new(
+                    format!("update.channels.{}", name),
+                    "Braucht entweder branch oder tag.",
+                )),
+            }
+        }
+
+        for (key, value) in [
+            (
+                "crawl.requests_per_minute",
+                self.crawl.requests_per_minute as usize,
+            ),
+            ("crawl.max_media_size", self.crawl.max_media_size as usize),
+            ("update.interval", self.update.interval as usize),
+            ("update.health_grace", self.update.health_grace as usize),
+            ("control.backlog_size", self.control.backlog_size),
+            (
+                "control.connection_timeout",
+                self.control.connection_timeout as usize,
+            ),
+            (
+                "control.session_timeout",
+                self.control.session_timeout as usize,
+            ),
+            ("control.max_connections", self.control.max_connections),
+            ("control.max_listeners", self.control.max_listeners),
+        ] {
+            if value == 0 {
+                errors.push(Issue::new(key, "Muss größer als 0 sein."));
+            }
+        }
+
+        errors
+    }
+
+    /// Kombinationen die zwar gültig, aber vermutlich nicht gewollt sind
+    pub fn warnings(&self) -> Vec<Issue> {
+        let mut warnings = Vec::new();
+
+        if self.update.interval < 60 {
+            warnings.push(Issue::new(
+                "update.interval",
+                format!(
+                    "Alle {} s nach Updates zu suchen belastet das Repository unnötig, Standard \
+                     sind {} s.",
+                    self.update.interval,
+                    settings::UPDATE_FETCH_DELAY.as_secs()
+                ),
+            ));
+        }
+
+        let mut targets = BTreeSet::new();
+        for target in &self.crawl.targets {
+            if !targets.insert(target) {
+                warnings.push(Issue::new(
+                    "crawl.targets",
+                    format!(
+                        "\"{}\" steht mehrmals in der Liste und wird entsprechend öfter abgefragt.",
+                        target
+                    ),
+                ));
+            }
+        }
+
+        let rules = &self.crawl.rules;
+        for community in &rules.allow_communities {
+            if rules.deny_communities.contains(community) {
+                warnings.push(Issue::new(
+                    "crawl.rules.deny_communities",
+                    format!(
+                        "{} ist gleichzeitig erlaubt und ausgeschlossen, sie wird ausgeschlossen.",
+                        community
+                    ),
+                ));
+            }
+        }
+
+        if self.control.session_timeout < self.control.connection_timeout {
+            warnings.push(Issue::new(
+                "control.session_timeout",
+                "Ist kürzer als control.connection_timeout, Sessions enden also vor einzelnen \
+                 Befehlen.",
+            ));
+        }
+        if self.control.max_listeners > self.control.max_connections {
+            warnings.push(Issue::new(
+                "control.max_listeners",
+                format!(
+                    "Ist größer als control.max_connections ({}), so viele Listener können nie \
+                     gleichzeitig verbunden sein.",
+                    self.control.max_connections
+                ),
+            ));
+        }
+
+        warnings
+    }
+
+    /// Warnungen für Ziele deren Instanz weniger Anfragen erlaubt als `crawl.requests_per_minute`.
+    /// Dafür wird jede Instanz nach ihren Limits gefragt, deshalb gehört das nicht zu `warnings`.
+    /// Instanzen die nicht antworten oder keine Limits veröffentlichen werden übersprungen.
+    pub fn rate_warnings(&self) -> Vec<Issue> {
+        let instances: BTreeSet<String> = self
+            .crawl
+            .targets
+            .iter()
+            .map(|target| instance_of(target))
+            .collect();
+
+        instances
+            .into_iter()
+            .filter_map(|instance| {
+                let limit = Lemmy::new(&instance).ok()?.requests_per_minute().ok()??;
+                (self.crawl.requests_per_minute > limit).then(|| {
+                    Issue::new(
+                        "crawl.requests_per_minute",
+                        format!(
+                            "{} erlaubt nur {} Anfragen pro Minute, es würden bis zu {} geschickt.",
+                            instance, limit, self.crawl.requests_per_minute
+                        ),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Gibt den Wert eines Schlüssels wie `update.interval` zurück
+    pub fn get(&self, key: &str) -> Result<toml::Value, String> {
+        let mut value = toml::Value::try_from(self).map_err(|err| err.to_string())?;
+        for part in key.split('.') {
+            value = value
+                .get(part)
+                .cloned()
+                .ok_or_else(|| format!("Unbekannter Schlüssel: {}", key))?;
+        }
+        Ok(value)
+    }
+}
+
+/// Die Konfiguration mit der das Programm gestartet wurde. Wurde sie nicht mit `init` gesetzt, wird
+/// sie beim ersten Aufruf geladen, ist sie ungültig wird mit dem Fehler beendet. Sie ändert sich
+/// danach nicht mehr, der Daemon hält für Werte die sich beim Neuladen ändern dürfen eine eigene
+/// Kopie. Alles was nur hier gelesen wird (z.B. die Pfade) wirkt deshalb erst nach einem Neustart.
+pub fn current() -> &'static Config {
+    CURRENT.get_or_init(|| {
+        Config::load().unwrap_or_else(|err| match ON_INVALID.get() {
+            Some(handler) => handler(&err),
+            None => {
+                eprintln!("{}", err);
+                exit(1);
+            }
+        })
+    })
+}
+
+/// Ersetzt was `current` mit einer ungültigen Konfiguration macht, statt den Fehler zu printen und
+/// zu exiten. Der Client gibt ihn so z.B. mit `--json` als JSON aus.
+pub fn on_invalid(handler: fn(&str) -> !) {
+    let _ = ON_INVALID.set(handler);
+}
+
+/// Setzt die Konfiguration für `current`, falls sie noch nicht geladen wurde
+pub fn init(config: Config) {
+    let _ = CURRENT.set(config);
+}
+
+/// Die Pfade aus der aktuellen Konfiguration
+pub fn paths() -> &'static PathsConfig {
+    &current().paths
+}
+
+/// Ob Client und Daemon im Benutzermodus laufen, also ohne root mit Pfaden nach der XDG Base
+/// Directory Specification. Der Client setzt USER_MODE_VAR bei `--user`, der Daemon erbt sie.
+pub fn user_mode() -> bool {
+    env::var(USER_MODE_VAR).is_ok_and(|value| value == "1")
+}
+
+/// Das Profil aus PROFILE_VAR, `None` ist das Standardprofil. Der Client setzt die Variable bei
+/// `--profile`, der Daemon erbt sie.
+pub fn profile() -> Option<String> {
+    env::var(PROFILE_VAR)
+        .ok()
+        .filter(|profile| !profile.is_empty())
+}
+
+/// Überprüft den Namen eines Profils, er landet in Pfaden und darf deshalb z.B. kein `/` enthalten
+pub fn parse_profile(name: &str) -> Result<String, String> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '-' || char == '_')
+    {
+        return Err(format!(
+            "Ungültiges Profil \"{}\", erlaubt sind Buchstaben, Ziffern, - und _.",
+            name
+        ));
+    }
+    Ok(name.to_string())
+}
+
+/// Überprüft ein `key=value` von `--set`
+pub fn parse_override(setting: &str) -> Result<String, String> {
+    match setting.split_once('=') {
+        Some((key, _)) if !key.trim().is_empty() => Ok(setting.to_string()),
+        _ => Err(format!("\"{}\" hat nicht die Form key=value.", setting)),
+    }
+}
+
+/// Das Verzeichnis der Konfiguration, im Benutzermodus in `$XDG_CONFIG_HOME`
+fn config_dir() -> String {
+    if user_mode() {
+        PathsConfig::file(&xdg_dir("XDG_CONFIG_HOME", ".config"), "feddit_archivieren")
+    } else {
+        Path::new(CONFIG_FILE)
+            .parent()
+            .map_or_else(String::new, |dir| dir.to_string_lossy().into_owned())
+    }
+}
+
+/// Wo die Konfigurationsdatei liegt. Profile haben ihre eigene in `profiles/<name>.toml`.
+pub fn config_file() -> String {
+    config_file_of(profile().as_deref())
+}
+
+/// Wo die Konfigurationsdatei von `profile` liegt, `None` ist das Standardprofil
+pub fn config_file_of(profile: Option<&str>) -> String {
+    match profile {
+        Some(profile) => PathsConfig::file(&config_dir(), &format!("profiles/{}.toml", profile)),
+        None => PathsConfig::file(&config_dir(), "config.toml"),
+    }
+}
+
+/// Die Pfade eines anderen Profils aus dessen Konfigurationsdatei. Umgebungsvariablen und `--set`
+/// gelten nur für das eigene Profil und werden hier nicht beachtet.
+pub fn paths_of(profile: Option<&str>) -> Result<PathsConfig, String> {
+    let path = config_file_of(profile);
+    let content = match read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(format!("Fehler beim Lesen von {}: {}", path, err)),
+    };
+    let table: toml::Table = content
+        .parse()
+        .map_err(|err| format!("{}: Ungültige Konfiguration: {}", path, err))?;
+
+    // Die Standardpfade hängen vom Profil ab, deshalb kann nicht einfach `Config::parse` verwendet werden
+    let mut paths =
+        toml::Table::try_from(PathsConfig::defaults(profile)).map_err(|err| err.to_string())?;
+    if let Some(section) = table.get("paths").and_then(toml::Value::as_table) {
+        paths.extend(section.clone());
+    }
+    toml::Value::Table(paths)
+        .try_into()
+        .map_err(|err| format!("{}: Ungültige Pfade: {}", path, err.to_string().trim()))
+}
+
+/// Alle Profile außer dem Standardprofil die eine Konfigurationsdatei oder ein Run-Verzeichnis an
+/// der Standardstelle haben, sortiert nach Namen
+pub fn profiles() -> Vec<String> {
+    let mut profiles = BTreeSet::new();
+
+    let dir = PathsConfig::file(&config_dir(), "profiles");
+    for entry in read_dir(dir).into_iter().flatten().map_while(Result::ok) {
+        if let Some(name) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_suffix(".toml"))
+        {
+            profiles.insert(name.to_string());
+        }
+    }
+
+    let run_dir = PathsConfig::defaults(None).run_dir;
+    let run_dir = Path::new(&run_dir);
+    if let (Some(parent), Some(name)) = (run_dir.parent(), run_dir.file_name()) {
+        let prefix = format!("{}@", name.to_string_lossy());
+        for entry in read_dir(parent).into_iter().flatten().map_while(Result::ok) {
+            if let Some(profile) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix(&prefix))
+            {
+                profiles.insert(profile.to_string());
+            }
+        }
+    }
+
+    profiles
+        .into_iter()
+        .filter(|profile| parse_profile(profile).is_ok())
+        .collect()
+}
+
+fn home() -> String {
+    env::var("HOME").unwrap_or_else(|_| env::temp_dir().to_string_lossy().into_owned())
+}
+
+/// Das XDG-Verzeichnis aus `var`, ist es nicht gesetzt (oder relativ, was die Spezifikation
+/// verbietet) wird `fallback` im Home-Verzeichnis verwendet
+fn xdg_dir(var: &str, fallback: &str) -> String {
+    match env::var(var) {
+        Ok(dir) if Path::new(&dir).is_absolute() => dir,
+        _ => PathsConfig::file(&home(), fallback),
+    }
+}
+
+/// `$XDG_RUNTIME_DIR` hat keinen Standardwert, ohne wird ein eigenes Verzeichnis in /tmp verwendet
+fn runtime_dir() -> String {
+    match env::var("XDG_RUNTIME_DIR") {
+        Ok(dir) if Path::new(&dir).is_absolute() => dir,
+        _ => PathsConfig::file(
+            &env::temp_dir().to_string_lossy(),
+            &format!("feddit_archivieren-{}", users::get_current_uid()),
+        ),
+    }
+}
+
+/// Liest die Konfigurationsdatei, existiert sie nicht wird ein leerer String zurückgegeben
+pub fn read_file() -> Result<String, String> {
+    let path = config_file();
+    match read_to_string(&path) {
+        Ok(content) => Ok(content),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(String::new()),
+        Err(err) => Err(format!("Fehler beim Lesen von {}: {}", path, err)),
+    }
+}
+
+/// Woher ein Wert der Konfiguration kommt. Spätere überschreiben frühere.
+#[derive(Clone)]
+pub enum Source {
+    Default,
+    File,
+    /// Die Umgebungsvariable
+    Env(String),
+    /// `--set` beim Client
+    Cli,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "Standardwert"),
+            Source::File => write!(f, "{}", config_file()),
+            Source::Env(var) => write!(f, "${}", var),
+            Source::Cli => write!(f, "--set"),
+        }
+    }
+}
+
+impl Source {
+    /// Der Name für `--json`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Source::Default => "default",
+            Source::File => "file",
+            Source::Env(_) => "env",
+            Source::Cli => "cli",
+        }
+    }
+}
+
+/// Lädt die Konfiguration in der Reihenfolge Standardwerte < Datei < Umgebungsvariablen < `--set`
+/// und überprüft sie. Zusätzlich wird zurückgegeben woher jeder nicht standardmäßige Schlüssel
+/// kommt.
+pub fn resolve() -> Result<(Config, BTreeMap<String, Source>), String> {
+    let content = read_file()?;
+    // Erst die Datei allein, damit Fehler darin mit Zeile gemeldet werden können
+    Config::parse(&content)?;
+    let mut table: toml::Table = content
+        .parse()
+        .map_err(|err| format!("Ungültige Konfiguration: {}", err))?;
+    let mut sources: BTreeMap<String, Source> = leaves(&table)
+        .into_iter()
+        .map(|(key, _)| (key, Source::File))
+        .collect();
+
+    let defaults = toml::Table::try_from(Config::default()).map_err(|err| err.to_string())?;
+    let mut vars: Vec<(String, String)> = env::vars()
+        .filter(|(var, _)| {
+            var.starts_with(ENV_PREFIX)
+                && ![USER_MODE_VAR, PROFILE_VAR, OVERRIDES_VAR].contains(&var.as_str())
+        })
+        .collect();
+    vars.sort();
+    let mut unknown = Vec::new();
+    for (var, value) in vars {
+        // Z.B. Variablen für eine andere Version sollen den Start nicht verhindern
+        let Some(key) =
+            env_key(&[&defaults, &table], &var[ENV_PREFIX.len()..]).filter(|key| known_key(key))
+        else {
+            unknown.push(var);
+            continue;
+        };
+        insert(&mut table, &key, &value).map_err(|err| format!("{}: {}", var, err))?;
+        sources.insert(key, Source::Env(var));
+    }
+    UNKNOWN_VARS_WARNED.call_once(|| {
+        for var in unknown {
+            eprintln!(
+                "Warnung: {}: Passt zu keinem Schlüssel, wird ignoriert.",
+                var
+            );
+        }
+    });
+
+    for line in env::var(OVERRIDES_VAR).unwrap_or_default().lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim();
+        insert(&mut table, key, value.trim()).map_err(|err| format!("--set {}: {}", key, err))?;
+        sources.insert(key.to_string(), Source::Cli);
+    }
+
+    let config: Config = toml::Value::Table(table).try_into().map_err(|err| {
+        format!(
+            "Ungültiger Wert aus der Umgebung oder --set: {}",
+            err.to_string().trim()
+        )
+    })?;
+    let errors = config.errors();
+    if !errors.is_empty() {
+        return Err(errors
+            .iter()
+            .map(|issue| describe(issue, &sources))
+            .collect::<Vec<_>>()
+            .join("\n"));
+    }
+    Ok((config, sources))
+}
+
+/// Ein Fehler oder eine Warnung zu einem Schlüssel der Konfiguration
+pub struct Issue {
+    pub key: String,
+    pub message: String,
+}
+
+impl Issue {
+    fn new(key: impl Into<String>, message: impl Into<String>) -> Issue {
+        Issue {
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+/// Woher der Wert von `key` kommt. Wurde eine ganze Sektion gesetzt, gilt das für alle Werte darin,
+/// bei einer Sektion selbst zählt der erste gesetzte Wert darin.
+pub fn source_of(sources: &BTreeMap<String, Source>, key: &str) -> Source {
+    let section = format!("{}.", key);
+    key.match_indices('.')
+        .map(|(index, _)| &key[..index])
+        .chain([key])
+        .filter_map(|prefix| sources.get(prefix))
+        .next_back()
+        .or_else(|| {
+            sources
+                .range(section.clone()..)
+                .next()
+                .filter(|(child, _)| child.starts_with(&section))
+                .map(|(_, source)| source)
+        })
+        .cloned()
+        .unwrap_or(Source::Default)
+}
+
+/// Beschreibt ein Problem mit der Stelle an der der Wert gesetzt wurde, bei der Datei mit Zeile
+pub fn describe(issue: &Issue, sources: &BTreeMap<String, Source>) -> String {
+    match source_of(sources, &issue.key) {
+        Source::File => {
+            let content = read_file().unwrap_or_default();
+            match line_of(&content, &issue.key) {
+                Some(line) => format!("{}:{}: {}", config_file(), line, issue),
+                None => format!("{}: {}", config_file(), issue),
+            }
+        }
+        Source::Default => format!("{} (Standardwert)", issue),
+        source => format!("{}: {}", source, issue),
+    }
+}
+
+/// Die Zeile in der `offset` liegt, beginnend bei 1
+fn line(content: &str, offset: usize) -> usize {
+    content[..offset.min(content.len())].matches('\n').count() + 1
+}
+
+/// Die Zeile in der `key` gesetzt wird, oder die seiner nächsten Sektion die in der Datei steht
+fn line_of(content: &str, key: &str) -> Option<usize> {
+    let document = ImDocument::parse(content).ok()?;
+    let mut table: &dyn TableLike = document.as_table();
+    let mut span = None;
+
+    for part in key.split('.') {
+        let Some((key, item)) = table.get_key_value(part) else {
+            break;
+        };
+        span = key.span().or(item.span()).or(span);
+        match item.as_table_like() {
+            Some(section) => table = section,
+            None => break,
+        }
+    }
+
+    span.map(|span| line(content, span.start))
+}
+
+/// Der Schlüssel in dessen Name oder Wert `offset` liegt
+fn key_at(content: &str, offset: usize) -> Option<String> {
+    fn find(table: &dyn TableLike, offset: usize, prefix: &str) -> Option<String> {
+        for (name, item) in table.iter() {
+            let path = match prefix {
+                "" => name.to_string(),
+                prefix => format!("{}.{}", prefix, name),
+            };
+            if let Some(found) = item
+                .as_table_like()
+                .and_then(|section| find(section, offset, &path))
+            {
+                return Some(found);
+            }
+
+            let key = table.get_key_value(name).map(|(key, _)| key);
+            if [key.and_then(Key::span), item.span()]
+                .into_iter()
+                .flatten()
+                .any(|span| span.contains(&offset))
+            {
+                return Some(path);
+            }
+        }
+        None
+    }
+
+    let document = ImDocument::parse(content).ok()?;
+    find(document.as_table(), offset, "")
+}
+
+/// Alle Werte einer Tabelle mit ihren vollen Schlüsseln wie `update.interval`. Listen zählen als
+/// ein Wert.
+pub fn leaves(table: &toml::Table) -> Vec<(String, toml::Value)> {
+    let mut leaves = Vec::new();
+    for (key, value) in table {
+        match value {
+            toml::Value::Table(section) => {
+                for (subkey, value) in self::leaves(section) {
+                    leaves.push((format!("{}.{}", key, subkey), value));
+                }
+            }
+            value => leaves.push((key.clone(), value.clone())),
+        }
+    }
+    leaves
+}
+
+/// Findet den Schlüssel zu einer Umgebungsvariable ohne Präfix, z.B. `control.backlog_size` für
+/// `CONTROL_BACKLOG_SIZE`. Weil Schlüssel selbst `_` enthalten, wird auf jeder Ebene die längste
+/// passende Sektion genommen und der Rest ist der Schlüssel darin.
+fn env_key(tables: &[&toml::Table], name: &str) -> Option<String> {
+    let name = name.to_lowercase();
+    let parts: Vec<&str> = name.split('_').collect();
+    let mut rest = &parts[..];
+    let mut tables = tables.to_vec();
+    let mut key = Vec::new();
+
+    'sections: while rest.len() > 1 {
+        for length in (1..rest.len()).rev() {
+            let section = rest[..length].join("_");
+            let sections: Vec<&toml::Table> = tables
+                .iter()
+                .filter_map(|table| table.get(&section)?.as_table())
+                .collect();
+            if !sections.is_empty() {
+                key.push(section);
+                tables = sections;
+                rest = &rest[length..];
+                continue 'sections;
+            }
+        }
+        break;
+    }
+
+    // Auf der obersten Ebene gibt es nur Sektionen
+    if key.is_empty() || rest.is_empty() {
+        return None;
+    }
+    key.push(rest.join("_"));
+    Some(key.join("."))
+}
+
+/// Ob es `key` in der Konfiguration gibt. Optionale Schlüssel wie `update.pin` fehlen in den
+/// Standardwerten, deshalb wird serde gefragt: Ein unbekannter Schlüssel ist ein anderer Fehler als
+/// ein Wert mit falschem Typ.
+fn known_key(key: &str) -> bool {
+    let mut table = toml::Table::new();
+    if insert(&mut table, key, "{}").is_err() {
+        return false;
+    }
+    match toml::Value::Table(table).try_into::<Config>() {
+        Ok(_) => true,
+        Err(err) => !err.message().starts_with("unknown field"),
+    }
+}
+
+/// Überprüft ob ein Verzeichnis oder eine Datei unter `path` liegen oder angelegt werden kann
+fn reachable(path: &str, directory: bool) -> Result<(), String> {
+    let path = Path::new(path);
+    if path.exists() {
+        return match (directory, path.is_dir()) {
+            (true, false) => Err(format!("{:?} existiert, ist aber kein Verzeichnis.", path)),
+            (false, true) => Err(format!("{:?} ist ein Verzeichnis.", path)),
+            _ => Ok(()),
+        };
+    }
+
+    // Fehlendes wird erst beim Start angelegt, das geht nur in einem Verzeichnis
+    let Some(parent) = path.ancestors().skip(1).find(|ancestor| ancestor.exists()) else {
+        return Ok(());
+    };
+    if !parent.is_dir() {
+        return Err(format!(
+            "{:?} kann nicht angelegt werden, {:?} ist kein Verzeichnis.",
+            path, parent
+        ));
+    }
+    // Wer die Installation nicht verändern darf, legt dort sowieso nichts an
+    if privileged() && !writable(parent) {
+        return Err(format!(
+            "{:?} kann nicht angelegt werden, keine Schreibrechte für {:?}.",
+            path, parent
+        ));
+    }
+    Ok(())
+}
+
+#[allow(clippy::macro_metavars_in_unsafe)]
+fn writable(path: &Path) -> bool {
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    trust_me_bro! { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
+
+/// Liest `value` als TOML-Wert. Was nicht nach TOML aussieht, z.B. ein Pfad, wird als String
+/// genommen. Angefangene Listen, Tabellen oder Strings wie `[1,2` sind dagegen ein Fehler.
+fn parse_value(value: &str) -> Result<toml::Value, String> {
+    match toml::Value::deserialize(toml::de::ValueDeserializer::new(value)) {
+        Ok(parsed) => Ok(parsed),
+        Err(err) if value.trim_start().starts_with(['[', '{', '"', '\'']) => Err(format!(
+            "Ungültiger Wert {}: {}",
+            value,
+            err.message().trim().replace('\n', ", ")
+        )),
+        Err(_) => Ok(toml::Value::String(value.to_string())),
+    }
+}
+
+/// Setzt `key` in einer Tabelle, Sektionen werden bei Bedarf erstellt. `value` wird mit
+/// `parse_value` gelesen.
+fn insert(table: &mut toml::Table, key: &str, value: &str) -> Result<(), String> {
+    let value = parse_value(value)?;
+
+    let parts: Vec<&str> = key.split('.').collect();
+    let (last, sections) = parts.split_last().unwrap();
+    let mut current = table;
+    for section in sections {
+        current = current
+            .entry(section.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| format!("{} ist keine Sektion.", section))?;
+    }
+    current.insert(last.to_string(), value);
+    Ok(())
+}
+
+/// Trägt `version` in `update.skip` in der Konfigurationsdatei ein
+pub fn skip_version(version: &Version) -> Result<Config, String> {
+    let mut versions = Config::parse(&read_file()?)?.update.skip;
+    if !versions.contains(version) {
+        versions.push(version.clone());
+    }
+    let value = versions
+        .iter()
+        .map(|version| format!("\"{}\"", version))
+        .collect::<Vec<_>>()
+        .join(", ");
+    set("update.skip", &format!("[{}]", value))
+}
+
+/// Liest die Konfigurationsdatei so, dass sie mit Kommentaren und Reihenfolge wieder geschrieben
+/// werden kann
+fn read_document() -> Result<DocumentMut, String> {
+    read_file()?
+        .parse()
+        .map_err(|err| format!("Ungültige Konfiguration: {}", err))
+}
+
+/// Entfernt `key` aus der Konfigurationsdatei, danach gilt wieder der Standardwert
+pub fn unset(key: &str) -> Result<Config, String> {
+    let mut document = read_document()?;
+
+    let parts: Vec<&str> = key.split('.').collect();
+    let (last, sections) = parts.split_last().unwrap();
+    let mut current: Option<&mut dyn TableLike> = Some(document.as_table_mut());
+    for section in sections {
+        current = current
+            .and_then(|table| table.get_mut(section))
+            .and_then(Item::as_table_like_mut);
+    }
+    if let Some(section) = current {
+        section.remove(last);
+    }
+
+    write_file(document)
+}
+
+/// Setzt `key` in der Konfigurationsdatei auf `value`, siehe `parse_value`. Der Rest der Datei
+/// bleibt wie er ist, auch Kommentare. Die Datei wird nur geschrieben, wenn die neue Konfiguration
+/// gültig ist.
+pub fn set(key: &str, value: &str) -> Result<Config, String> {
+    let mut value: toml_edit::Value = parse_value(value)?
+        .to_string()
+        .parse()
+        .map_err(|err| format!("Ungültiger Wert: {}", err))?;
+    let mut document = read_document()?;
+
+    let parts: Vec<&str> = key.split('.').collect();
+    let (last, sections) = parts.split_last().unwrap();
+    let mut current: &mut dyn TableLike = document.as_table_mut();
+    for section in sections {
+        current = current
+            .entry(section)
+            .or_insert(toml_edit::table())
+            .as_table_like_mut()
+            .ok_or_else(|| format!("{} ist keine Sektion.", section))?;
+    }
+    match current.get_mut(last) {
+        // Ein Kommentar hinter dem alten Wert bleibt stehen
+        Some(Item::Value(old)) => {
+            *value.decor_mut() = old.decor().clone();
+            *old = value;
+        }
+        _ => {
+            current.insert(last, Item::Value(value));
+        }
+    }
+
+    write_file(document)
+}
+
+/// Schreibt `document` in die Konfigurationsdatei, aber nur wenn es eine gültige Konfiguration ist
+fn write_file(document: DocumentMut) -> Result<Config, String> {
+    let content = document.to_string();
+    let config: Config = toml::from_str(&content)
+        .map_err(|err| format!("Ungültige Konfiguration: {}", err.message().trim()))?;
+    config.validate()?;
+
+    let path = config_file();
+    if let Some(dir) = Path::new(&path).parent() {
+        create_dir_all(dir)
+            .map_err(|err| format!("Fehler beim Erstellen von {:?}: {}", dir, err))?;
+    }
+    write(&path, content).map_err(|err| format!("Fehler beim Schreiben von {}: {}", path, err))?;
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_parses_toml_values() {
+        let mut table = toml::Table::new();
+        insert(&mut table, "update.interval", "60").unwrap();
+        insert(&mut table, "crawl.targets", r#"["https://feddit.org"]"#).unwrap();
+        insert(&mut table, "paths.data_dir", "/srv/archiv").unwrap();
+
+        assert_eq!(table["update"]["interval"].as_integer(), Some(60));
+        assert_eq!(
+            table["crawl"]["targets"][0].as_str(),
+            Some("https://feddit.org")
+        );
+        // Was kein TOML ist, ist ein String
+        assert_eq!(table["paths"]["data_dir"].as_str(), Some("/srv/archiv"));
+    }
+
+    #[test]
+    fn durations_from_override() {
+        // Wie bei --set update.interval=2h oder FEDDIT_ARCHIVIEREN_UPDATE_INTERVAL=2h
+        let mut table = toml::Table::new();
+        insert(&mut table, "update.interval", "2h").unwrap();
+        insert(&mut table, "update.health_grace", "90").unwrap();
+        insert(&mut table, "control.session_timeout", "10m").unwrap();
+        let config: Config = toml::Value::Table(table).try_into().unwrap();
+
+        assert_eq!(config.update.interval, 7200);
+        assert_eq!(config.update.health_grace, 90);
+        assert_eq!(config.control.session_timeout, 600);
+    }
+
+    #[test]
+    fn durations_in_file() {
+        let config = Config::parse("[update]\ninterval = \"1d\"\nhealth_grace = 30\n").unwrap();
+        assert_eq!(config.update.interval, 86400);
+        assert_eq!(config.update.health_grace, 30);
+
+        assert!(Config::parse("[update]\ninterval = \"2 Stunden\"\n").is_err());
+        assert!(Config::parse("[update]\ninterval = -5\n").is_err());
+        assert!(Config::parse("[update]\ninterval = true\n").is_err());
+    }
+
+    #[test]
+    fn insert_rejects_broken_toml() {
+        let mut table = toml::Table::new();
+        assert!(insert(&mut table, "crawl.targets", "[1,2").is_err());
+        assert!(insert(&mut table, "log.level", "\"warn").is_err());
+        assert!(table.is_empty());
+    }
+
+    /// Eine gültige Konfiguration deren Pfade unabhängig vom System erreichbar sind
+    fn valid() -> Config {
+        let dir = env::temp_dir().to_string_lossy().into_owned();
+        let file = |name| PathsConfig::file(&dir, name);
+        Config {
+            paths: PathsConfig {
+                run_dir: dir.clone(),
+                data_dir: dir.clone(),
+                update_dir: dir.clone(),
+                update_cache_dir: dir.clone(),
+                daemon_binary: file("feddit_archivieren_test_daemon"),
+                client_binary: file("feddit_archivieren_test_client"),
+            },
+            ..Config::default()
+        }
+    }
+
+    fn error_keys(config: &Config) -> Vec<String> {
+        config.errors().into_iter().map(|issue| issue.key).collect()
+    }
+
+    #[test]
+    fn errors_valid_config() {
+        assert!(error_keys(&valid()).is_empty());
+    }
+
+    #[test]
+    fn errors_name_the_key() {
+        let mut config = valid();
+        config.paths.data_dir = "relativ".to_string();
+        config.crawl.targets = vec!["ftp://feddit.org".to_string()];
+        config.update.interval = 0;
+        config.update.channel = "gibtsnicht".to_string();
+        assert_eq!(
+            error_keys(&config),
+            [
+                "paths.data_dir",
+                "crawl.targets",
+                "update.channel",
+                "update.interval"
+            ]
+        );
+
+        let mut config = valid();
+        let version = Version::new(1, 2, 3);
+        config.update.pin = Some(version.clone());
+        config.update.skip = vec![version];
+        config.crawl.targets.clear();
+        assert_eq!(error_keys(&config), ["crawl.targets", "update.pin"]);
+    }
+
+    #[test]
+    fn env_key_takes_longest_section() {
+        let defaults = toml::Table::try_from(valid()).unwrap();
+        let key = |name| env_key(&[&defaults], name);
+
+        assert_eq!(
+            key("CONTROL_BACKLOG_SIZE").as_deref(),
+            Some("control.backlog_size")
+        );
+        assert_eq!(
+            key("CRAWL_RULES_EXCLUDE_NSFW").as_deref(),
+            Some("crawl.rules.exclude_nsfw")
+        );
+        // Sektionen die nur in der Datei stehen zählen auch
+        let file: toml::Table = "[update.channels.test]\ntag = \"v*\"".parse().unwrap();
+        assert_eq!(
+            env_key(&[&defaults, &file], "UPDATE_CHANNELS_TEST_TAG").as_deref(),
+            Some("update.channels.test.tag")
+        );
+        assert_eq!(key("GIBTSNICHT"), None);
+        assert_eq!(key("UPDATE"), None);
+    }
+
+    #[test]
+    fn known_keys() {
+        assert!(known_key("update.interval"));
+        // Optionale Schlüssel stehen nicht in den Standardwerten
+        assert!(known_key("update.pin"));
+        assert!(known_key("crawl.rules.min_score"));
+        assert!(!known_key("update.gibtsnicht"));
+        assert!(!known_key("gibtsnicht.interval"));
+    }
+
+    #[test]
+    fn source_of_later_layers() {
+        let sources = BTreeMap::from([
+            ("update.interval".to_string(), Source::File),
+            (
+                "update.channels".to_string(),
+                Source::Env("VAR".to_string()),
+            ),
+            ("update.channels.stable.branch".to_string(), Source::Cli),
+        ]);
+        let source = |key| source_of(&sources, key).name();
+
+        assert_eq!(source("update.interval"), "file");
+        assert_eq!(source("update.channels.beta.tag"), "env");
+        assert_eq!(source("update.channels.stable.branch"), "cli");
+        // Eine Sektion zählt wie ihr erster gesetzter Wert
+        assert_eq!(source("update"), "env");
+        assert_eq!(source("control.backlog_size"), "default");
+    }
+
+    #[test]
+    fn insert_into_value_is_no_section() {
+        let mut table = toml::Table::new();
+        insert(&mut table, "update", "1").unwrap();
+        assert!(insert(&mut table, "update.interval", "60").is_err());
+    }
+}
@@ -1,26 +1,51 @@
-use colored::{ColoredString, Colorize};
+use chrono::{DateTime, Local};
 use daemonize::Daemonize;
 use helpers::root;
 use std::{
+    collections::VecDeque,
     fs::File,
-    io::{BufWriter, ErrorKind, Write},
+    io::{BufRead, BufReader, BufWriter, Cursor, ErrorKind, Read, Write},
     net::{TcpListener, TcpStream},
     process::exit,
-    sync::{Arc, Mutex},
-    thread::{self, sleep},
-    time::Duration,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, sleep, JoinHandle},
+    time::{Duration, Instant},
 };
+mod archive;
+mod config;
 mod helpers;
+mod lemmy;
+mod log;
+mod pause;
+mod rules;
+mod search;
 mod settings;
+mod status;
 
 use crate::{
-    helpers::{chmod, daemon_running, read_from_stream, update},
-    settings::{ERR_FILE, OUT_FILE, PID_FILE, POST_FILE, SOCKET_FILE, URL_FILE},
+    archive::{Post, Revision, Thread},
+    config::{paths, Config},
+    helpers::{chmod, daemon_running, instance_of, read_from_stream, time_ago, update},
+    lemmy::Lemmy,
+    log::{Entry, Level},
+    pause::{Pauses, Scope},
+    rules::Rules,
+    search::{search, SearchQuery},
+    status::{Status, TargetStatus, UpdateCheck},
 };
 
-macro_rules! unwrap_mutex_save {
+/// Einträge unter diesem Level werden nicht ins Log geschrieben, kommt aus `log.level`
+static LOG_LEVEL: Mutex<Level> = Mutex::new(Level::Info);
+
+/// Wird vom Signalhandler für SIGHUP gesetzt, der Reload-Thread lädt dann die Konfiguration neu
+static SIGHUP_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+macro_rules! lock_mutex_save {
     ($e:expr) => {
-        *match $e.lock() {
+        match $e.lock() {
             Ok(lock) => lock,
             Err(poison) => {
                 println!("Der Mutex ist gepoisent: {}", poison);
@@ -31,26 +56,84 @@ macro_rules! unwrap_mutex_save {
     }
 }
 
-/// streams, guard, running_guard, url, posts_guard, feddit_guard, archive_guard
-macro_rules! shutdown {
-    ($stream:expr, $guard:expr, $running_guard:expr, $url:expr, $posts_guard:expr, $feddit_guard:expr, $archive_guard:expr) => {
-        print("Stoppe den Daemon.", $guard.clone());
-        unwrap_mutex_save!($running_guard) = false;
-        shutdown_preperations(&*$guard.lock().unwrap(), $url, $posts_guard);
-        wait_with_timeout!(
-            || unwrap_mutex_save!($feddit_guard).is_finished().clone()
-                && unwrap_mutex_save!($archive_guard).is_finished().clone(),
-            Duration::from_secs(1)
-        );
-        $stream.write_all(b"ok").unwrap();
-        println!("Exite.");
-        exit(0);
+macro_rules! unwrap_mutex_save {
+    ($e:expr) => {
+        *lock_mutex_save!($e)
     };
 }
 
+/// Alle Listener des Daemons, zusammen mit den letzten Ausgaben die an sie gingen
+struct Recievers {
+    streams: Vec<Listener>,
+    backlog: VecDeque<(DateTime<Local>, String)>,
+    /// Wie viele Nachrichten das Backlog maximal enthält, kann sich beim Neuladen ändern
+    backlog_size: usize,
+}
+
+type Streams = Arc<Mutex<Recievers>>;
+
+/// Ein Stream an den die Ausgaben des Daemons weitergeleitet werden
+struct Listener {
+    stream: TcpStream,
+    /// Wird vor jede Nachricht geschrieben, damit Sessions Events von Antworten unterscheiden können
+    prefix: &'static str,
+}
+
+/// Ein Post den der Crawler gefunden hat, der aber noch nicht archiviert wurde
+struct Queued {
+    instance: String,
+    community: String,
+    id: i32,
+}
+
+/// Alles was die Verbindungs-Threads vom Daemon brauchen
+#[derive(Clone)]
+struct Daemon {
+    recievers: Streams,
+    /// Die Warteschlange zwischen dem Feddit- und dem Archive-Thread
+    posts: Arc<Mutex<VecDeque<Queued>>>,
+    running: Arc<Mutex<bool>>,
+    status: Arc<Mutex<Status>>,
+    pauses: Arc<Mutex<Pauses>>,
+    config: Arc<Mutex<Config>>,
+    /// `crawl.rules` aus `config`, kompiliert beim Start und bei jedem Neuladen
+    rules: Arc<Mutex<Rules>>,
+    /// Die beiden Threads bekommen selbst den Daemon, deshalb werden sie erst danach gestartet
+    feddit: Arc<Mutex<Option<JoinHandle<()>>>>,
+    archive: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+/// Was nach einem Befehl mit der Verbindung passieren soll
+enum Reply {
+    /// Die Antwort wird geschrieben
+    Text(String),
+    /// Der Daemon wird neu gestartet
+    Restart,
+    /// Der Daemon wird gestoppt
+    Stop,
+    /// Die Verbindung wird zum Listener, vorher wird das Backlog nachgeliefert
+    Listen(Option<Duration>, Option<usize>),
+    /// Der Post zur URL wird sofort archiviert, währenddessen wird der Fortschritt geschickt
+    ArchiveNow(String),
+    /// Das Archiv wird durchsucht, die Treffer werden einzeln geschickt
+    Search(SearchQuery),
+}
+
 fn main() {
-    let url = settings::FEDDIT_LINK;
-    let posts: Arc<Mutex<Vec<i32>>> = Arc::new(Mutex::new(Vec::new()));
+    // Mit einer ungültigen Konfiguration wird gar nicht erst gestartet
+    let (config, sources) = match config::resolve() {
+        Ok(resolved) => resolved,
+        Err(err) => {
+            println!("{}", err);
+            exit(1);
+        }
+    };
+    for warning in config.warnings() {
+        println!("Warnung: {}", config::describe(&warning, &sources));
+    }
+    // Pfade und alles andere was erst nach einem Neustart wirkt kommt ab hier aus config::current
+    config::init(config.clone());
+    let paths = paths();
 
     // Überprüfen ob bereits ein Daemon läuft
     if daemon_running() {
@@ -60,8 +143,33 @@ fn main() {
         exit(1);
     }
 
+    // Die Regeln werden einmal kompiliert statt für jeden Post
+    let rules = match Rules::new(&config.crawl.rules) {
+        Ok(rules) => Arc::new(Mutex::new(rules)),
+        Err(err) => {
+            println!("{}", err);
+            exit(1);
+        }
+    };
+
+    // Pausen vom letzten Lauf laden, damit nicht versehentlich weitergecrawlt wird
+    let pauses = match Pauses::load() {
+        Ok(pauses) => Arc::new(Mutex::new(pauses)),
+        Err(err) => {
+            println!("{}", err);
+            exit(1);
+        }
+    };
+
+    // Die Logs vom letzten Lauf aufheben
+    for file in [paths.out_file(), paths.err_file()] {
+        if let Err(err) = log::rotate(&file) {
+            println!("{}", err);
+        }
+    }
+
     // Den Daemon erstellen und starten
-    let stdout = match File::create(OUT_FILE) {
+    let stdout = match File::create(paths.out_file()) {
         Ok(stdout) => stdout,
         Err(err) => {
             if err.kind() == ErrorKind::PermissionDenied {
@@ -73,28 +181,36 @@ fn main() {
         }
     };
 
-    File::create(URL_FILE).unwrap();
-    File::create(POST_FILE).unwrap();
-    File::create(PID_FILE).unwrap();
-    let stderr = File::create(ERR_FILE).unwrap();
+    File::create(paths.url_file()).unwrap();
+    File::create(paths.post_file()).unwrap();
+    File::create(paths.pid_file()).unwrap();
+    let stderr = File::create(paths.err_file()).unwrap();
 
     let daemonize = Daemonize::new()
-        .pid_file(PID_FILE)
+        .pid_file(paths.pid_file())
         .working_directory(".")
         .stdout(stdout)
         .stderr(stderr);
 
-    chmod_to_non_root(OUT_FILE);
-    chmod_to_non_root(ERR_FILE);
-    chmod_to_non_root(PID_FILE);
-    chmod_to_non_root(URL_FILE);
-    chmod_to_non_root(POST_FILE);
+    chmod_to_non_root(&paths.out_file());
+    chmod_to_non_root(&paths.err_file());
+    chmod_to_non_root(&paths.pid_file());
+    chmod_to_non_root(&paths.url_file());
+    chmod_to_non_root(&paths.post_file());
 
-    let recievers: Arc<Mutex<Vec<TcpStream>>> = Arc::new(Mutex::new(Vec::new()));
+    let recievers: Streams = Arc::new(Mutex::new(Recievers {
+        streams: Vec::new(),
+        backlog: VecDeque::with_capacity(config.control.backlog_size),
+        backlog_size: config.control.backlog_size,
+    }));
+    *lock_mutex_save!(LOG_LEVEL) = config.log.level;
+    lemmy::set_requests_per_minute(config.crawl.requests_per_minute);
+    let status = Arc::new(Mutex::new(Status::new(&config.crawl.targets)));
+    let config = Arc::new(Mutex::new(config));
 
     match daemonize.start() {
-        Ok(_) => println!("Daemon erfolgreich gestartet."),
-        Err(e) => eprintln!("Error, {}", e),
+        Ok(_) => write_log(Level::Info, "daemon", "Daemon erfolgreich gestartet."),
+        Err(e) => write_log(Level::Error, "daemon", &format!("Error, {}", e)),
     }
 
     // An einen Socket binden
@@ -105,156 +221,717 @@ fn main() {
         .local_addr()
         .expect("Fehler beim Holen der Socket Adresse.");
 
-    println!("Erfolgreich an einen Socket gebunden.");
+    write_log(
+        Level::Info,
+        "control",
+        "Erfolgreich an einen Socket gebunden.",
+    );
 
     // Unsere Socketadresse ins Socketfile schreiben
-    let mut socketfile = File::create(SOCKET_FILE).unwrap();
-    chmod_to_non_root(SOCKET_FILE);
+    let mut socketfile = File::create(paths.socket_file()).unwrap();
+    chmod_to_non_root(&paths.socket_file());
     socketfile
         .write_all(socket.to_string().as_bytes())
         .expect("Fehler beim Schreiben ins Socketfile.");
 
-    println!("Socketadresse in eine Datei geschrieben.");
+    write_log(
+        Level::Info,
+        "control",
+        "Socketadresse in eine Datei geschrieben.",
+    );
 
-    let running = Arc::new(Mutex::new(true));
+    let daemon = Daemon {
+        recievers,
+        posts: Arc::new(Mutex::new(VecDeque::new())),
+        running: Arc::new(Mutex::new(true)),
+        status,
+        pauses,
+        config,
+        rules,
+        feddit: Arc::new(Mutex::new(None)),
+        archive: Arc::new(Mutex::new(None)),
+    };
 
-    let guard = running.clone();
-    let archive = Arc::new(Mutex::new(thread::spawn(|| archive(guard))));
-    let guard = running.clone();
-    let feddit = Arc::new(Mutex::new(thread::spawn(|| feddit(guard))));
+    let daemon_guard = daemon.clone();
+    *lock_mutex_save!(daemon.archive) = Some(thread::spawn(move || archive(daemon_guard)));
+    let daemon_guard = daemon.clone();
+    *lock_mutex_save!(daemon.feddit) = Some(thread::spawn(move || feddit(daemon_guard)));
 
     // Update Thread spawnen
-    let guard = recievers.clone();
+    let guard = daemon.recievers.clone();
+    let status_guard = daemon.status.clone();
+    let config_guard = daemon.config.clone();
     thread::spawn(move || loop {
+        let update_config = lock_mutex_save!(config_guard).update.clone();
+        let interval = update_config.interval;
         print(
+            "updater",
             &format!(
                 "Warte {} Sekunden vor der nächsten Updateüberprüfung...",
-                settings::UPDATE_FETCH_DELAY.as_secs()
+                interval
             ),
             guard.clone(),
         );
-        sleep(settings::UPDATE_FETCH_DELAY);
-        print("Update...", guard.clone());
-        if let Err(err) = update(Some(print), Some(guard.clone())) {
-            eprint(format!("{}", err).as_str(), guard.clone());
+        sleep(Duration::from_secs(interval));
+        print("updater", "Update...", guard.clone());
+        // Der Daemon updatet nie auf ältere Versionen, das geht nur mit dem Client und --force.
+        // Installiert wird mit `client install`, der diesen Daemon ersetzt, den neuen
+        // `update.health_grace` lang beobachtet und bei Problemen zurückrollt. Dafür muss die
+        // Installation diesen Prozess überleben, sie läuft deshalb losgelöst in einer eigenen
+        // Session und wird nicht abgewartet.
+        let result = update(
+            &update_config,
+            false,
+            true,
+            Some(|message, streams| print("updater", message, streams)),
+            Some(guard.clone()),
+        );
+        if let Err(err) = &result {
+            eprint("updater", err, guard.clone());
+        }
+        unwrap_mutex_save!(status_guard).last_update_check = Some(UpdateCheck {
+            time: Local::now(),
+            error: result.err(),
+        });
+    });
+
+    // SIGHUP lädt die Konfiguration neu, wie der reload Befehl. Im Signalhandler selbst darf kaum
+    // etwas passieren, deshalb wird nur ein Flag gesetzt.
+    catch_sighup();
+    let daemon_guard = daemon.clone();
+    thread::spawn(move || loop {
+        sleep(Duration::from_millis(500));
+        if SIGHUP_RECEIVED.swap(false, Ordering::SeqCst) {
+            print(
+                "control",
+                "SIGHUP empfangen, lade die Konfiguration neu.",
+                daemon_guard.recievers.clone(),
+            );
+            let _ = reload(&daemon_guard);
         }
     });
 
     // Auf reinkommende Befehl hören
+    let connections = Arc::new(AtomicUsize::new(0));
     for stream in listener.incoming() {
-        let guard = recievers.clone();
-        let posts_guard = posts.clone();
-        let running_guard = running.clone();
-        let feddit_guard = feddit.clone();
-        let archive_guard = archive.clone();
-        thread::spawn(move || match stream {
+        let mut stream = match stream {
+            Ok(stream) => stream,
             Err(err) => {
                 eprint(
+                    "control",
                     &format!("Fehlerhafte Verbindung empfangen: {}", err),
-                    guard.clone(),
+                    daemon.recievers.clone(),
                 );
-                return;
+                continue;
             }
-            Ok(mut stream) => {
-                print(
-                    &format!("Empfange Verbindung mit {}...", stream.peer_addr().unwrap()),
+        };
+
+        let control = lock_mutex_save!(daemon.config).control.clone();
+        let timeout = Duration::from_secs(control.connection_timeout);
+
+        // Ohne Timeouts könnte ein Client der nichts sendet einen Thread für immer blockieren
+        if let Err(err) = stream
+            .set_read_timeout(Some(timeout))
+            .and_then(|_| stream.set_write_timeout(Some(timeout)))
+        {
+            eprint(
+                "control",
+                &format!("Fehler beim Setzen der Timeouts: {}", err),
+                daemon.recievers.clone(),
+            );
+            continue;
+        }
+
+        let slot = ConnectionSlot::new(connections.clone());
+        if slot.count > control.max_connections {
+            warn(
+                "control",
+                &format!(
+                    "Lehne Verbindung ab, es gibt bereits {} Verbindungen.",
+                    control.max_connections
+                ),
+                daemon.recievers.clone(),
+            );
+            let _ = stream.write_all(b"rejected Zu viele Verbindungen.");
+            continue;
+        }
+
+        let daemon = daemon.clone();
+        thread::spawn(move || {
+            handle_connection(daemon, stream);
+            drop(slot);
+        });
+    }
+}
+
+#[allow(clippy::macro_metavars_in_unsafe)]
+fn catch_sighup() {
+    trust_me_bro! {
+        libc::signal(libc::SIGHUP, handle_sighup as *const () as libc::sighandler_t);
+    }
+}
+
+extern "C" fn handle_sighup(_: libc::c_int) {
+    SIGHUP_RECEIVED.store(true, Ordering::SeqCst);
+}
+
+/// Lädt die Konfiguration neu und übernimmt alles was im laufenden Betrieb geht: Ziele, Intervalle,
+/// Limits und das Loglevel. Returnt die geänderten Schlüssel die erst nach einem Neustart wirken.
+fn reload(daemon: &Daemon) -> Result<Vec<&'static str>, String> {
+    let guard = daemon.recievers.clone();
+
+    let loaded = Config::load().and_then(|config| {
+        let rules = Rules::new(&config.crawl.rules)?;
+        Ok((config, rules))
+    });
+    let (config, rules) = match loaded {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            eprint(
+                "control",
+                &format!("Konfiguration nicht neu geladen: {}", err),
+                guard,
+            );
+            return Err(err);
+        }
+    };
+
+    let mut current = lock_mutex_save!(daemon.config);
+
+    if config.crawl.targets != current.crawl.targets {
+        // Der Fortschritt von Zielen die bleiben wird übernommen
+        let mut status = lock_mutex_save!(daemon.status);
+        status.targets = config
+            .crawl
+            .targets
+            .iter()
+            .map(|url| {
+                status
+                    .targets
+                    .iter()
+                    .find(|target| target.url == *url)
+                    .cloned()
+                    .unwrap_or_else(|| TargetStatus::new(url))
+            })
+            .collect();
+        print(
+            "control",
+            &format!("Crawle ab jetzt: {}", config.crawl.targets.join(", ")),
+            guard.clone(),
+        );
+    }
+    lock_mutex_save!(daemon.recievers).backlog_size = config.control.backlog_size;
+    *lock_mutex_save!(LOG_LEVEL) = config.log.level;
+    lemmy::set_requests_per_minute(config.crawl.requests_per_minute);
+
+    // Verglichen wird mit der Konfiguration beim Start, denn nur die gilt für diese Schlüssel
+    let started = config::current();
+    let restart: Vec<&'static str> = [
+        ("paths", config.paths != started.paths),
+        (
+            "log.rotations",
+            config.log.rotations != started.log.rotations,
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, changed)| *changed)
+    .map(|(key, _)| key)
+    .collect();
+    for key in &restart {
+        warn(
+            "control",
+            &format!(
+                "Änderungen an {} werden erst nach einem Neustart übernommen.",
+                key
+            ),
+            guard.clone(),
+        );
+    }
+
+    *current = config;
+    *lock_mutex_save!(daemon.rules) = rules;
+    print("control", "Konfiguration neu geladen.", guard);
+    Ok(restart)
+}
+
+/// Zählt eine offene Verbindung, solange er existiert
+struct ConnectionSlot {
+    connections: Arc<AtomicUsize>,
+    /// Die Anzahl der Verbindungen inklusive dieser
+    count: usize,
+}
+
+impl ConnectionSlot {
+    fn new(connections: Arc<AtomicUsize>) -> Self {
+        let count = connections.fetch_add(1, Ordering::SeqCst) + 1;
+        ConnectionSlot { connections, count }
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Bearbeitet eine Verbindung. Normalerweise wird genau ein Befehl ausgeführt, beginnt die
+/// Verbindung aber mit `session`, werden so lange Befehle entgegengenommen bis der Client sie
+/// schließt.
+fn handle_connection(daemon: Daemon, mut stream: TcpStream) {
+    let guard = daemon.recievers.clone();
+
+    print(
+        "control",
+        &format!("Empfange Verbindung mit {}...", stream.peer_addr().unwrap()),
+        guard.clone(),
+    );
+
+    let message = read_from_stream(&mut stream);
+    if message.is_empty() {
+        print(
+            "control",
+            "Keine Nachricht empfangen, schließe die Verbindung.",
+            guard,
+        );
+        return;
+    }
+
+    print(
+        "control",
+        &format!("Nachricht: \"{}\"", message),
+        guard.clone(),
+    );
+
+    let (first_line, pending) = message.split_once('\n').unwrap_or((&message, ""));
+    let (command, args) = split_command(first_line);
+
+    if command == "session" {
+        // Sessions dürfen zwischen zwei Befehlen länger still sein
+        let timeout = lock_mutex_save!(daemon.config).control.session_timeout;
+        if let Err(err) = stream.set_read_timeout(Some(Duration::from_secs(timeout))) {
+            eprint(
+                "control",
+                &format!("Fehler beim Setzen der Timeouts: {}", err),
+                guard,
+            );
+            return;
+        }
+        session(&daemon, stream, pending.to_string());
+        return;
+    }
+
+    match execute(&daemon, command, args) {
+        Reply::Text(text) => {
+            if let Err(err) = stream.write_all(text.as_bytes()) {
+                eprint("control", &format!("Fehler beim Antworten: {}", err), guard);
+            }
+        }
+        Reply::Restart => {
+            stream.write_all(b"ok").unwrap();
+            shutdown(&daemon);
+            stream.write_all(b"ok").unwrap();
+            write_log(Level::Info, "daemon", "Exite.");
+            exit(0);
+        }
+        Reply::Stop => {
+            shutdown(&daemon);
+            stream.write_all(b"ok").unwrap();
+            write_log(Level::Info, "daemon", "Exite.");
+            exit(0);
+        }
+        Reply::Listen(since, last) => {
+            if let Err(err) = subscribe(&daemon, stream, None, since, last) {
+                eprint("control", &err, guard);
+            }
+        }
+        Reply::ArchiveNow(url) => {
+            let result = archive_now(&daemon, &url, |message| {
+                let _ = stream.write_all(format!("progress {}\n", message).as_bytes());
+            });
+            let response = match result {
+                Ok(id) => format!("ok {}", id),
+                Err(err) => format!("error {}", err),
+            };
+            if let Err(err) = stream.write_all(response.as_bytes()) {
+                eprint("control", &format!("Fehler beim Antworten: {}", err), guard);
+            }
+        }
+        Reply::Search(query) => {
+            let response = stream_search(&daemon, &query, |line| {
+                let _ = stream.write_all(format!("{}\n", line).as_bytes());
+            });
+            if let Err(err) = stream.write_all(response.as_bytes()) {
+                eprint("control", &format!("Fehler beim Antworten: {}", err), guard);
+            }
+        }
+    }
+}
+
+/// Nimmt Befehle zeilenweise in der Form `<id> <befehl> [argumente]` entgegen und antwortet jeweils
+/// mit `<id> <antwort>`. Nach `<id> listen` werden zusätzlich alle Ausgaben des Daemons als
+/// `event <nachricht>` über dieselbe Verbindung geschickt.
+fn session(daemon: &Daemon, stream: TcpStream, pending: String) {
+    let guard = daemon.recievers.clone();
+
+    let reader = match stream.try_clone() {
+        Ok(reader) => reader,
+        Err(err) => {
+            eprint(
+                "control",
+                &format!("Fehler beim Klonen eines Streams: {}", err),
+                guard,
+            );
+            return;
+        }
+    };
+    let mut writer = &stream;
+
+    for line in BufReader::new(Cursor::new(pending).chain(reader)).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                eprint(
+                    "control",
+                    &format!("Fehler beim Lesen aus einer Session: {}", err),
                     guard.clone(),
                 );
+                break;
+            }
+        };
 
-                let message = read_from_stream(&mut stream);
+        let (id, rest) = split_command(&line);
+        if id.is_empty() {
+            continue;
+        }
+        let (command, args) = split_command(rest);
 
-                print(&format!("Nachricht: \"{}\"", message), guard.clone());
+        print(
+            "control",
+            &format!("Session-Befehl {}: \"{}\"", id, command),
+            guard.clone(),
+        );
 
-                match message.as_str() {
-                    "ping" => {
-                        print("Schreibe 'pong' in den stream", guard);
-                        stream.write_all(b"pong").unwrap();
-                    }
-                    "restart" => {
-                        print("restart", guard.clone());
-                        stream.write_all(b"ok").unwrap();
-                        shutdown!(
-                            stream,
-                            guard,
-                            running_guard,
-                            url,
-                            posts_guard,
-                            feddit_guard,
-                            archive_guard
-                        );
+        let response = match execute(daemon, command, args) {
+            Reply::Text(text) => text,
+            Reply::Restart | Reply::Stop => {
+                let _ = writer.write_all(format!("{} ok\n", id).as_bytes());
+                shutdown(daemon);
+                write_log(Level::Info, "daemon", "Exite.");
+                exit(0);
+            }
+            Reply::Listen(since, last) => match stream.try_clone() {
+                Ok(events) => {
+                    // subscribe schreibt die Antwort selbst, damit sie vor dem Backlog ankommt
+                    if let Err(err) = subscribe(daemon, events, Some(id), since, last) {
+                        eprint("control", &err, guard.clone());
+                        continue;
                     }
-                    "stop" => {
-                        shutdown!(
-                            stream,
-                            guard,
-                            running_guard,
-                            url,
-                            posts_guard,
-                            feddit_guard,
-                            archive_guard
+                    // Wer zuhört schickt unter Umständen lange nichts, das Session-Timeout gilt nicht mehr
+                    if let Err(err) = stream.set_read_timeout(None) {
+                        eprint(
+                            "control",
+                            &format!("Fehler beim Setzen der Timeouts: {}", err),
+                            guard.clone(),
                         );
                     }
-                    "listen" => {
-                        stream.write_all(b"Hallo!").unwrap();
+                    continue;
+                }
+                Err(err) => format!("error {}", err),
+            },
+            Reply::ArchiveNow(url) => {
+                let result = archive_now(daemon, &url, |message| {
+                    let _ = writer.write_all(format!("{} progress {}\n", id, message).as_bytes());
+                });
+                match result {
+                    Ok(archive_id) => format!("ok {}", archive_id),
+                    Err(err) => format!("error {}", err),
+                }
+            }
+            Reply::Search(query) => stream_search(daemon, &query, |line| {
+                let _ = writer.write_all(format!("{} {}\n", id, line).as_bytes());
+            }),
+        };
+
+        if let Err(err) = writer.write_all(format!("{} {}\n", id, response).as_bytes()) {
+            eprint(
+                "control",
+                &format!("Fehler beim Antworten: {}", err),
+                guard.clone(),
+            );
+            break;
+        }
+    }
+
+    // Der Client hat die Session beendet, also brauchen wir auch keine Events mehr zu schicken
+    let peer = stream.peer_addr().ok();
+    lock_mutex_save!(guard)
+        .streams
+        .retain(|listener| listener.stream.peer_addr().ok() != peer);
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+}
+
+/// Führt einen Befehl aus und returnt was mit der Verbindung passieren soll
+fn execute(daemon: &Daemon, command: &str, args: &str) -> Reply {
+    let guard = daemon.recievers.clone();
 
-                        guard.lock().unwrap().push(stream);
+    match command {
+        "ping" => {
+            print("control", "Schreibe 'pong' in den stream", guard);
+            Reply::Text("pong".to_string())
+        }
+        "restart" => {
+            print("control", "restart", guard);
+            Reply::Restart
+        }
+        "stop" => Reply::Stop,
+        "status" => {
+            let mut status = unwrap_mutex_save!(daemon.status).clone();
+            status.uptime = (Local::now() - status.started).num_seconds();
+            status.queue = unwrap_mutex_save!(daemon.posts).len();
+            status.paused = lock_mutex_save!(daemon.pauses).list();
+            match serde_json::to_string(&status) {
+                Ok(json) => Reply::Text(json),
+                Err(err) => Reply::Text(format!("error {}", err)),
+            }
+        }
+        "listen" => match parse_listen_args(args) {
+            Ok((since, last)) => Reply::Listen(since, last),
+            Err(err) => {
+                eprint("control", &err, guard);
+                Reply::Text(format!("rejected {}", err))
+            }
+        },
+        "pause" | "resume" => {
+            let scope = match args.split_whitespace().collect::<Vec<_>>()[..] {
+                [] if command == "pause" => Ok(Some(Scope::All)),
+                [] => Ok(None),
+                [scope] => scope.parse().map(Some),
+                _ => Err("Zu viele Argumente.".to_string()),
+            };
+
+            let result = scope.and_then(|scope| {
+                let mut pauses = lock_mutex_save!(daemon.pauses);
+                match scope {
+                    Some(scope) if command == "pause" => {
+                        print("control", &format!("Pausiere {}.", scope), guard.clone());
+                        pauses.pause(scope)
                     }
-                    _ => {
-                        println!("Unbekannter Befehl.");
-                        stream.write_all(b"unknown").unwrap();
+                    scope => {
+                        let name = scope.as_ref().map_or("*".to_string(), Scope::to_string);
+                        print("control", &format!("Setze {} fort.", name), guard.clone());
+                        pauses.resume(scope)
                     }
                 }
+            });
+
+            match result {
+                Ok(()) => Reply::Text("ok".to_string()),
+                Err(err) => {
+                    eprint("control", &err, guard);
+                    Reply::Text(err)
+                }
             }
-        });
+        }
+        "reload" => match reload(daemon) {
+            Ok(restart) if restart.is_empty() => Reply::Text("ok".to_string()),
+            Ok(restart) => Reply::Text(format!("ok restart {}", restart.join(","))),
+            Err(err) => Reply::Text(format!("error {}", err)),
+        },
+        "archive-now" => match args.split_whitespace().collect::<Vec<_>>()[..] {
+            [url] => Reply::ArchiveNow(url.to_string()),
+            _ => Reply::Text("error Erwartet genau eine URL.".to_string()),
+        },
+        "search" => match serde_json::from_str(args) {
+            Ok(query) => Reply::Search(query),
+            Err(err) => Reply::Text(format!("error Ungültige Suche: {}", err)),
+        },
+        _ => {
+            warn(
+                "control",
+                &format!("Unbekannter Befehl: \"{}\"", command),
+                guard,
+            );
+            Reply::Text("unknown".to_string())
+        }
     }
 }
 
-fn print(message: &str, streams: Arc<Mutex<Vec<TcpStream>>>) {
-    println!("{}", message);
+/// Teilt eine Zeile in das erste Wort und den Rest
+fn split_command(line: &str) -> (&str, &str) {
+    let line = line.trim();
+    match line.split_once(char::is_whitespace) {
+        Some((command, args)) => (command, args.trim_start()),
+        None => (line, ""),
+    }
+}
+
+/// Stoppt alle Threads und speichert den Fortschritt, bevor der Daemon exitet
+fn shutdown(daemon: &Daemon) {
+    print("daemon", "Stoppe den Daemon.", daemon.recievers.clone());
+    unwrap_mutex_save!(daemon.running) = false;
+    let url = lock_mutex_save!(daemon.config).crawl.targets[0].clone();
+    shutdown_preperations(
+        &lock_mutex_save!(daemon.recievers).streams,
+        &url,
+        daemon.posts.clone(),
+    );
+    let finished = |handle: &Arc<Mutex<Option<JoinHandle<()>>>>| {
+        lock_mutex_save!(handle)
+            .as_ref()
+            .map_or(true, JoinHandle::is_finished)
+    };
+    wait_with_timeout!(
+        || finished(&daemon.feddit) && finished(&daemon.archive),
+        Duration::from_secs(1)
+    );
+}
 
-    let mut streams_override_idxs = Vec::new();
+/// Fügt einen Stream zu den Listenern hinzu, nachdem die Bestätigung und das passende Backlog
+/// hineingeschrieben wurden. Gibt es schon zu viele Listener, wird stattdessen eine Ablehnung
+/// geschrieben. Kommt die Anfrage aus einer Session, wird die Antwort mit `session_id` markiert
+/// und alle Ausgaben mit `event `.
+fn subscribe(
+    daemon: &Daemon,
+    mut stream: TcpStream,
+    session_id: Option<&str>,
+    since: Option<Duration>,
+    last: Option<usize>,
+) -> Result<(), String> {
+    let (greeting, reply_prefix, prefix) = match session_id {
+        Some(id) => (format!("{} ok\n", id), format!("{} ", id), "event "),
+        None => ("Hallo!\n".to_string(), String::new(), ""),
+    };
 
-    for (index, mut stream) in unwrap_mutex_save!(streams).iter().enumerate() {
-        if let Err(err) = stream.write(message.as_bytes()) {
-            eprintln!("Fehler beim Schreiben in einen Stream: {}", err);
-        } else {
-            streams_override_idxs.push(index);
-        }
+    let max_listeners = lock_mutex_save!(daemon.config).control.max_listeners;
+
+    // Solange der Lock gehalten wird kann keine neue Nachricht dazwischenkommen
+    let mut recievers = lock_mutex_save!(daemon.recievers);
+
+    if recievers.streams.len() >= max_listeners {
+        let message = format!(
+            "Lehne Listener ab, es gibt bereits {} Listener.",
+            max_listeners
+        );
+        let _ = stream.write_all(format!("{}rejected {}\n", reply_prefix, message).as_bytes());
+        return Err(message);
     }
 
-    let new: Vec<TcpStream> = unwrap_mutex_save!(streams)
-        .drain(..)
-        .enumerate()
-        .filter(|(idx, _)| streams_override_idxs.contains(idx))
-        .map(|(_, stream)| stream)
-        .collect();
-    unwrap_mutex_save!(streams) = new;
+    stream
+        .write_all(greeting.as_bytes())
+        .and_then(|_| replay(&mut stream, prefix, &recievers.backlog, since, last))
+        .map_err(|err| format!("Fehler beim Schreiben in einen Stream: {}", err))?;
+
+    recievers.streams.push(Listener { stream, prefix });
+    Ok(())
+}
+
+fn print(component: &str, message: &str, streams: Streams) {
+    write_log(Level::Info, component, message);
+    broadcast(message, streams);
+}
 
-    sleep(Duration::from_millis(1));
+fn warn(component: &str, message: &str, streams: Streams) {
+    write_log(Level::Warn, component, message);
+    broadcast(message, streams);
 }
 
-fn eprint(message: &str, streams: Arc<Mutex<Vec<TcpStream>>>) {
-    eprintln!("{}", message);
+fn eprint(component: &str, message: &str, streams: Streams) {
+    write_log(Level::Error, component, message);
+    broadcast(message, streams);
+}
 
-    let mut streams_override_idxs = Vec::new();
+/// Schreibt eine Zeile ins Log, Infos landen in `out_file`, alles andere in `err_file`
+fn write_log(level: Level, component: &str, message: &str) {
+    if level < *lock_mutex_save!(LOG_LEVEL) {
+        return;
+    }
 
-    for (index, mut stream) in unwrap_mutex_save!(streams).iter().enumerate() {
-        if let Err(err) = stream.write(message.as_bytes()) {
-            eprintln!("Fehler beim Schreiben in einen Stream: {}", err);
+    let entry = Entry::now(level, component, message);
+    match level {
+        Level::Info => println!("{}", entry),
+        Level::Warn | Level::Error => eprintln!("{}", entry),
+    }
+}
+
+/// Schreibt eine Nachricht an alle Listener und merkt sie sich im Backlog. Listener bei denen das
+/// Schreiben fehlschlägt werden entfernt.
+fn broadcast(message: &str, streams: Streams) {
+    let mut recievers = lock_mutex_save!(streams);
+
+    recievers.streams.retain_mut(|listener| {
+        let line = format!("{}{}\n", listener.prefix, message);
+        if let Err(err) = listener.stream.write_all(line.as_bytes()) {
+            write_log(
+                Level::Error,
+                "control",
+                &format!("Fehler beim Schreiben in einen Stream: {}", err),
+            );
+            false
         } else {
-            streams_override_idxs.push(index);
+            true
         }
+    });
+
+    while recievers.backlog.len() >= recievers.backlog_size {
+        recievers.backlog.pop_front();
     }
+    recievers
+        .backlog
+        .push_back((Local::now(), message.to_string()));
+}
+
+/// Parst die Argumente von `listen`, also `since=<Sekunden>` und `last=<Anzahl>`
+fn parse_listen_args(args: &str) -> Result<(Option<Duration>, Option<usize>), String> {
+    let mut since = None;
+    let mut last = None;
+
+    for arg in args.split_whitespace() {
+        match arg.split_once('=') {
+            Some(("since", value)) => match value.parse() {
+                Ok(secs) if time_ago(Duration::from_secs(secs)).is_some() => {
+                    since = Some(Duration::from_secs(secs))
+                }
+                Ok(secs) => return Err(format!("since={} liegt zu weit zurück.", secs)),
+                Err(err) => return Err(format!("Ungültiger Wert für since: {}", err)),
+            },
+            Some(("last", value)) => match value.parse() {
+                Ok(count) => last = Some(count),
+                Err(err) => return Err(format!("Ungültiger Wert für last: {}", err)),
+            },
+            _ => return Err(format!("Unbekanntes Argument für listen: {}", arg)),
+        }
+    }
+
+    Ok((since, last))
+}
 
-    let new: Vec<TcpStream> = unwrap_mutex_save!(streams)
-        .drain(..)
-        .enumerate()
-        .filter(|(idx, _)| streams_override_idxs.contains(idx))
-        .map(|(_, stream)| stream)
+/// Schreibt die Nachrichten aus dem Backlog die jünger als `since` sind in den Stream, maximal aber
+/// die letzten `last`. Ohne beide Angaben wird nichts geschrieben.
+fn replay(
+    stream: &mut TcpStream,
+    prefix: &str,
+    backlog: &VecDeque<(DateTime<Local>, String)>,
+    since: Option<Duration>,
+    last: Option<usize>,
+) -> Result<(), std::io::Error> {
+    if since.is_none() && last.is_none() {
+        return Ok(());
+    }
+
+    // Nicht darstellbare Zeitpunkte lehnt schon parse_listen_args ab
+    let cutoff = since.and_then(time_ago);
+    let matching: Vec<_> = backlog
+        .iter()
+        .filter(|(time, _)| cutoff.map_or(true, |cutoff| *time >= cutoff))
         .collect();
-    unwrap_mutex_save!(streams) = new;
+    let skip = matching.len().saturating_sub(last.unwrap_or(usize::MAX));
+
+    for (time, message) in &matching[skip..] {
+        let line = format!("{}[{}] {}\n", prefix, time.format("%H:%M:%S"), message);
+        stream.write_all(line.as_bytes())?;
+    }
 
-    sleep(Duration::from_millis(1));
+    Ok(())
 }
 
 /// Ändert die Berechtigungen einer Datei zu read-write für alle Nutzer
@@ -265,48 +942,357 @@ fn chmod_to_non_root(filepath: &str) {
 }
 
 /// Wird ausgeführt nachdem stop empfangen wurde
-fn shutdown_preperations(streams: &Vec<TcpStream>, url: &str, posts: Arc<Mutex<Vec<i32>>>) {
-    for mut stream in streams {
-        stream.write(b"Tschau :)").unwrap();
-        stream.shutdown(std::net::Shutdown::Both).unwrap();
+fn shutdown_preperations(streams: &[Listener], url: &str, posts: Arc<Mutex<VecDeque<Queued>>>) {
+    for listener in streams {
+        let mut stream = &listener.stream;
+        let _ = stream.write_all(format!("{}Tschau :)\n", listener.prefix).as_bytes());
+        let _ = stream.shutdown(std::net::Shutdown::Both);
     }
 
-    if let Err(err) = save(url, unwrap_mutex_save!(posts).to_vec()) {
-        println!("Fehler beim Speichern: {}", err);
+    if let Err(err) = save(url, &unwrap_mutex_save!(posts)) {
+        write_log(
+            Level::Error,
+            "daemon",
+            &format!("Fehler beim Speichern: {}", err),
+        );
     }
 }
 
-/// Speichert den aktuellen Fortschritt
-fn save(url: &str, posts: Vec<i32>) -> Result<(), std::io::Error> {
-    let mut url_file = File::create(settings::URL_FILE)?;
-    let post_file = File::create(settings::POST_FILE)?;
+/// Speichert den aktuellen Fortschritt, die Warteschlange als eine Zeile `<instanz>/<id>` pro Post
+fn save(url: &str, posts: &VecDeque<Queued>) -> Result<(), std::io::Error> {
+    let mut url_file = File::create(paths().url_file())?;
+    let post_file = File::create(paths().post_file())?;
 
     url_file.write_all(url.as_bytes())?;
 
     let mut writer = BufWriter::new(post_file);
     for post in posts {
-        writer.write_all(post.to_string().as_bytes())?;
+        writeln!(writer, "{}/{}", post.instance, post.id)?;
     }
 
+    writer.flush()
+}
+
+/// Wendet die Regeln aus `crawl.rules` an, bevor ein Post in die Warteschlange kommt und noch einmal
+/// bevor er archiviert wird. Übersprungene Posts werden mit Grund geloggt, damit sich später
+/// nachvollziehen lässt warum etwas fehlt.
+fn admit(daemon: &Daemon, instance: &str, post: &Post) -> Result<(), String> {
+    lock_mutex_save!(daemon.rules)
+        .check(post)
+        .map_err(|reason| {
+            print(
+                "crawler",
+                &format!(
+                    "Überspringe {}/{} (\"{}\"): {}",
+                    instance, post.id, post.title, reason
+                ),
+                daemon.recievers.clone(),
+            );
+            format!("Übersprungen wegen crawl.rules: {}", reason)
+        })
+}
+
+/// Durchsucht das Archiv und schickt jeden Treffer als eigene Zeile `hit <json>` an `send`, damit
+/// große Antworten nicht abgeschnitten werden. Übersprungene Threads kommen als `warning <grund>`.
+/// Returnt die letzte Zeile, `ok` oder `error <grund>`.
+fn stream_search(daemon: &Daemon, query: &SearchQuery, mut send: impl FnMut(&str)) -> String {
+    let guard = daemon.recievers.clone();
+
+    let hits = search(query, |err| {
+        warn(
+            "control",
+            &format!("Überspringe Thread bei der Suche: {}", err),
+            guard.clone(),
+        );
+        send(&format!("warning {}", err));
+    });
+
+    match hits {
+        Ok(hits) => {
+            for hit in hits {
+                match serde_json::to_string(&hit) {
+                    Ok(json) => send(&format!("hit {}", json)),
+                    Err(err) => return format!("error {}", err),
+                }
+            }
+            "ok".to_string()
+        }
+        Err(err) => {
+            eprint("control", &err, guard);
+            format!("error {}", err)
+        }
+    }
+}
+
+/// Lehnt ab wenn `instance` bzw. `community` gerade pausiert ist
+fn ensure_not_paused(
+    daemon: &Daemon,
+    instance: &str,
+    community: Option<&str>,
+) -> Result<(), String> {
+    if lock_mutex_save!(daemon.pauses).is_paused(instance, community) {
+        return Err(match community {
+            Some(community) => format!("{} auf {} ist pausiert.", community, instance),
+            None => format!("{} ist pausiert.", instance),
+        });
+    }
     Ok(())
 }
 
-/// Funktion die vom Archive-Thread ausgeführt wird
-fn archive(running: Arc<Mutex<bool>>) {
+/// Archiviert den Post zu einer Post- oder Kommentar-URL sofort, an der Warteschlange vorbei.
+/// `progress` bekommt jeden Schritt mit. Returnt die Archiv-ID in der Form `<instanz>/<post id>`.
+fn archive_now(
+    daemon: &Daemon,
+    url: &str,
+    mut progress: impl FnMut(&str),
+) -> Result<String, String> {
+    let guard = daemon.recievers.clone();
+    let mut step = |message: &str| {
+        print("archiver", message, guard.clone());
+        progress(message);
+    };
+
+    let result = (|| {
+        let lemmy = Lemmy::for_url(url)?;
+        ensure_not_paused(daemon, lemmy.instance(), None)?;
+        step(&format!("Löse {} auf {} auf...", url, lemmy.instance()));
+        let id = lemmy.resolve(url)?;
+        archive_post(daemon, &lemmy, id, step)
+    })();
+
+    count_archived(daemon, url, &result);
+    result
+}
+
+/// Archiviert einen Post mit Kommentaren und Medien als neue Revision, falls er sich seit der
+/// letzten geändert hat. Returnt die Archiv-ID in der Form `<instanz>/<post id>`.
+fn archive_post(
+    daemon: &Daemon,
+    lemmy: &Lemmy,
+    id: i32,
+    mut step: impl FnMut(&str),
+) -> Result<String, String> {
+    step(&format!("Hole Post {}...", id));
+    let (post, media) = lemmy.post(id)?;
+    // Die Community steht erst im Post
+    ensure_not_paused(daemon, lemmy.instance(), Some(&post.community))?;
+    admit(daemon, lemmy.instance(), &post)?;
+    step("Hole Kommentare...");
+    let comments = lemmy.comments(id)?;
+    step(&format!("{} Kommentare geholt.", comments.len()));
+
+    let max_media_size = lock_mutex_save!(daemon.config).crawl.max_media_size;
+    for media_url in media {
+        match lemmy.download_media(id, &media_url, max_media_size) {
+            Ok(Some(path)) => step(&format!("Medien gespeichert: {:?}", path)),
+            Ok(None) => {}
+            // Fehlende Medien sollen nicht den ganzen Post verhindern
+            Err(err) => step(&format!("Fehler bei den Medien: {}", err)),
+        }
+    }
+
+    let mut thread = Thread::load(lemmy.instance(), id)?.unwrap_or_else(|| Thread {
+        instance: lemmy.instance().to_string(),
+        id,
+        revisions: Vec::new(),
+    });
+    let unchanged = thread
+        .revisions
+        .last()
+        .is_some_and(|latest| latest.post == post && latest.comments == comments);
+    if unchanged {
+        step("Keine Änderungen seit der letzten Archivierung.");
+    } else {
+        thread.revisions.push(Revision {
+            archived: Local::now(),
+            post,
+            comments,
+        });
+        thread.save()?;
+        step(&format!("Revision {} gespeichert.", thread.revisions.len()));
+    }
+
+    Ok(format!("{}/{}", thread.instance, thread.id))
+}
+
+/// Zählt eine Archivierung im Status, Fehler werden zusätzlich geloggt
+fn count_archived(daemon: &Daemon, url: &str, result: &Result<String, String>) {
+    let mut status = lock_mutex_save!(daemon.status);
+    match result {
+        Ok(_) => status.archived += 1,
+        Err(err) => {
+            status.archive_errors += 1;
+            eprint(
+                "archiver",
+                &format!("Fehler beim Archivieren von {}: {}", url, err),
+                daemon.recievers.clone(),
+            );
+        }
+    }
+}
+
+/// Funktion die vom Archive-Thread ausgeführt wird. Archiviert die Posts aus der Warteschlange der
+/// Reihe nach, pausierte werden übersprungen bis sie fortgesetzt werden.
+fn archive(daemon: Daemon) {
     loop {
-        if unwrap_mutex_save!(running) == false {
+        if !unwrap_mutex_save!(daemon.running) {
             return;
         }
         sleep(Duration::from_millis(50));
+
+        // Pausen werden nur zwischen zwei Posts beachtet, damit nichts halb archiviert wird
+        let next = {
+            let pauses = lock_mutex_save!(daemon.pauses);
+            if pauses.all() {
+                continue;
+            }
+            let mut posts = lock_mutex_save!(daemon.posts);
+            posts
+                .iter()
+                .position(|post| !pauses.is_paused(&post.instance, Some(&post.community)))
+                .and_then(|index| posts.remove(index))
+        };
+        let Some(post) = next else {
+            continue;
+        };
+
+        let url = format!("https://{}/post/{}", post.instance, post.id);
+        let guard = daemon.recievers.clone();
+        let result = Lemmy::new(&post.instance).and_then(|lemmy| {
+            archive_post(&daemon, &lemmy, post.id, |message| {
+                print("archiver", message, guard.clone())
+            })
+        });
+        count_archived(&daemon, &url, &result);
     }
 }
 
-/// Funktion die vom Feddit-Thread ausgeführt wird
-fn feddit(running: Arc<Mutex<bool>>) {
+/// Funktion die vom Feddit-Thread ausgeführt wird. Reihum wird von jedem Ziel die nächste Seite
+/// geholt, aber erst wenn die Warteschlange abgearbeitet ist und `CRAWL_DELAY` vergangen ist.
+fn feddit(daemon: Daemon) {
+    let mut last_round: Option<Instant> = None;
+
+    // Fragt die Instanzen, deshalb erst hier und nicht schon beim Start
+    let config = lock_mutex_save!(daemon.config).clone();
+    for warning in config.rate_warnings() {
+        warn("crawler", &warning.to_string(), daemon.recievers.clone());
+    }
+
     loop {
-        if unwrap_mutex_save!(running) == false {
+        if !unwrap_mutex_save!(daemon.running) {
             return;
         }
         sleep(Duration::from_millis(50));
+
+        let waiting = last_round.is_some_and(|last| last.elapsed() < settings::CRAWL_DELAY);
+        if waiting || !lock_mutex_save!(daemon.posts).is_empty() {
+            continue;
+        }
+        last_round = Some(Instant::now());
+
+        // Jede Runde neu, damit geänderte Ziele beim Neuladen sofort übernommen werden
+        let targets = lock_mutex_save!(daemon.config).crawl.targets.clone();
+
+        // Pausen werden nur zwischen zwei Seiten beachtet
+        for target in &targets {
+            if lock_mutex_save!(daemon.pauses).is_paused(&instance_of(target), None) {
+                continue;
+            }
+            crawl(&daemon, target);
+        }
+    }
+}
+
+/// Holt die nächste Seite von `target` und stellt die Posts, die `crawl.rules` erlaubt, in die
+/// Warteschlange. Der Fortschritt steht in `Status::targets`, ist die letzte Seite erreicht fängt
+/// das Ziel wieder vorne an.
+fn crawl(daemon: &Daemon, target: &str) {
+    let guard = daemon.recievers.clone();
+    let page = lock_mutex_save!(daemon.status)
+        .targets
+        .iter()
+        .find(|progress| progress.url == target)
+        .map(|progress| progress.page);
+    let Some(page) = page else {
+        return;
+    };
+
+    print(
+        "crawler",
+        &format!("Crawle Seite {} von {}...", page, target),
+        guard.clone(),
+    );
+    let result = Lemmy::for_url(target)
+        .and_then(|lemmy| Ok((lemmy.posts(target, page)?, lemmy.instance().to_string())));
+
+    // Das Ziel kann beim Neuladen während der Anfrage entfernt worden sein
+    let mut status = lock_mutex_save!(daemon.status);
+    let Some(progress) = status
+        .targets
+        .iter_mut()
+        .find(|progress| progress.url == target)
+    else {
+        return;
+    };
+    let (posts, instance) = match result {
+        Ok(fetched) => fetched,
+        Err(err) => {
+            progress.errors += 1;
+            drop(status);
+            eprint(
+                "crawler",
+                &format!("Fehler beim Crawlen von {}: {}", target, err),
+                guard,
+            );
+            return;
+        }
+    };
+
+    progress.last_fetch = Some(Local::now());
+    match posts.last() {
+        Some(last) => {
+            progress.page = page + 1;
+            progress.last_post = Some(last.id);
+        }
+        None => progress.page = TargetStatus::first_page(target),
+    }
+    drop(status);
+
+    if posts.is_empty() {
+        print(
+            "crawler",
+            &format!("Keine Posts mehr auf {}, fange wieder vorne an.", target),
+            guard,
+        );
+        return;
+    }
+
+    // Was die Regeln ablehnt kommt gar nicht erst in die Warteschlange
+    let admitted: Vec<Post> = posts
+        .into_iter()
+        .filter(|post| admit(daemon, &instance, post).is_ok())
+        .collect();
+
+    let mut queue = lock_mutex_save!(daemon.posts);
+    let mut queued = 0;
+    for post in admitted {
+        let known = queue
+            .iter()
+            .any(|other| other.instance == instance && other.id == post.id);
+        if !known {
+            queue.push_back(Queued {
+                instance: instance.clone(),
+                community: post.community,
+                id: post.id,
+            });
+            queued += 1;
+        }
     }
+    drop(queue);
+
+    print(
+        "crawler",
+        &format!("{} Posts von {} in der Warteschlange.", queued, target),
+        guard,
+    );
 }
@@ -0,0 +1,588 @@
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, File},
+    io::{BufWriter, Error, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local, Utc};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::archive::{self, removal_label, Comment, Revision, Thread};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    Json,
+    Csv,
+    Markdown,
+}
+
+/// Welche Threads exportiert werden
+pub struct Filter {
+    pub community: Option<String>,
+    pub instance: Option<String>,
+    pub since: Option<DateTime<Local>>,
+    pub until: Option<DateTime<Local>>,
+}
+
+/// Eine Zeile in `posts.*`. Die Feldnamen sind Teil des Exportformats und dürfen sich nicht ändern.
+/// Die CSV-Kopfzeile kommt aus `Default`, damit sie immer zu den Feldern passt.
+#[derive(Serialize, Default)]
+struct PostRow<'a> {
+    instance: &'a str,
+    post_id: i32,
+    community: &'a str,
+    author: &'a str,
+    title: &'a str,
+    body: Option<&'a str>,
+    url: Option<&'a str>,
+    score: i64,
+    published: DateTime<Utc>,
+    nsfw: bool,
+    deleted: bool,
+    removed: bool,
+    comments: usize,
+    archived: DateTime<Local>,
+}
+
+/// Eine Zeile in `comments.*`
+#[derive(Serialize, Default)]
+struct CommentRow<'a> {
+    instance: &'a str,
+    post_id: i32,
+    comment_id: i32,
+    parent_id: Option<i32>,
+    author: &'a str,
+    body: &'a str,
+    score: i64,
+    published: DateTime<Utc>,
+    deleted: bool,
+    removed: bool,
+}
+
+/// Eine Zeile in `authors.*`
+#[derive(Serialize, Clone, Default)]
+struct AuthorRow {
+    author: String,
+    posts: usize,
+    comments: usize,
+    first_seen: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+}
+
+/// Exportiert die neuste Revision aller passenden Threads nach `out`. Die Threads werden einzeln
+/// geladen und sofort geschrieben, nur die Autoren werden bis zum Schluss gesammelt.
+/// Threads die nicht gelesen werden können werden übersprungen und an `skipped` gegeben.
+/// Returnt die Anzahl der exportierten Threads.
+pub fn export(
+    format: Format,
+    filter: &Filter,
+    out: &Path,
+    mut skipped: impl FnMut(String),
+) -> Result<usize, String> {
+    create_dir_all(out).map_err(|err| format!("Fehler beim Erstellen von {:?}: {}", out, err))?;
+
+    let mut sink: Box<dyn Sink> = match format {
+        Format::Json => Box::new(JsonSink::new(out)?),
+        Format::Csv => Box::new(CsvSink::new(out)?),
+        Format::Markdown => Box::new(MarkdownSink::new(out)?),
+    };
+
+    let mut authors: BTreeMap<String, AuthorRow> = BTreeMap::new();
+    let mut count = 0;
+
+    for thread in archive::threads()? {
+        let thread = match thread {
+            Ok(thread) => thread,
+            Err(err) => {
+                skipped(err);
+                continue;
+            }
+        };
+        let Some(revision) = thread.latest() else {
+            skipped(format!(
+                "{}/{} hat keine Revision.",
+                thread.instance, thread.id
+            ));
+            continue;
+        };
+        if !filter.matches(&thread.instance, revision) {
+            continue;
+        }
+
+        let post = &revision.post;
+        see_author(&mut authors, &post.author, post.published).posts += 1;
+        for comment in &revision.comments {
+            see_author(&mut authors, &comment.author, comment.published).comments += 1;
+        }
+
+        sink.thread(&thread, revision)
+            .map_err(|err| format!("Fehler beim Schreiben des Exports: {}", err))?;
+        count += 1;
+    }
+
+    sink.finish(authors.into_values().collect())
+        .map_err(|err| format!("Fehler beim Schreiben des Exports: {}", err))?;
+
+    Ok(count)
+}
+
+impl Filter {
+    fn matches(&self, instance: &str, revision: &Revision) -> bool {
+        let post = &revision.post;
+        self.instance.as_ref().map_or(true, |i| i == instance)
+            && self
+                .community
+                .as_ref()
+                .map_or(true, |c| *c == post.community)
+            && self.since.map_or(true, |since| post.published >= since)
+            && self.until.map_or(true, |until| post.published <= until)
+    }
+}
+
+fn see_author<'a>(
+    authors: &'a mut BTreeMap<String, AuthorRow>,
+    author: &str,
+    time: DateTime<Utc>,
+) -> &'a mut AuthorRow {
+    let row = authors.entry(author.to_string()).or_insert(AuthorRow {
+        author: author.to_string(),
+        posts: 0,
+        comments: 0,
+        first_seen: time,
+        last_seen: time,
+    });
+    row.first_seen = row.first_seen.min(time);
+    row.last_seen = row.last_seen.max(time);
+    row
+}
+
+fn post_row<'a>(thread: &'a Thread, revision: &'a Revision) -> PostRow<'a> {
+    let post = &revision.post;
+    PostRow {
+        instance: &thread.instance,
+        post_id: post.id,
+        community: &post.community,
+        author: &post.author,
+        title: &post.title,
+        body: post.body.as_deref(),
+        url: post.url.as_deref(),
+        score: post.score,
+        published: post.published,
+        nsfw: post.nsfw,
+        deleted: post.deleted,
+        removed: post.removed,
+        comments: revision.comments.len(),
+        archived: revision.archived,
+    }
+}
+
+fn comment_row<'a>(thread: &'a Thread, comment: &'a Comment) -> CommentRow<'a> {
+    CommentRow {
+        instance: &thread.instance,
+        post_id: thread.id,
+        comment_id: comment.id,
+        parent_id: comment.parent,
+        author: &comment.author,
+        body: &comment.body,
+        score: comment.score,
+        published: comment.published,
+        deleted: comment.deleted,
+        removed: comment.removed,
+    }
+}
+
+fn create(path: PathBuf) -> Result<BufWriter<File>, String> {
+    File::create(&path)
+        .map(BufWriter::new)
+        .map_err(|err| format!("Fehler beim Erstellen von {:?}: {}", path, err))
+}
+
+/// Ein Exportformat
+trait Sink {
+    fn thread(&mut self, thread: &Thread, revision: &Revision) -> std::io::Result<()>;
+    fn finish(&mut self, authors: Vec<AuthorRow>) -> std::io::Result<()>;
+}
+
+/// Schreibt `posts.json`, `comments.json` und `authors.json`, jeweils ein Array von Objekten
+struct JsonSink {
+    posts: BufWriter<File>,
+    comments: BufWriter<File>,
+    authors: BufWriter<File>,
+    first_post: bool,
+    first_comment: bool,
+}
+
+impl JsonSink {
+    fn new(out: &Path) -> Result<Self, String> {
+        Ok(JsonSink {
+            posts: create(out.join("posts.json"))?,
+            comments: create(out.join("comments.json"))?,
+            authors: create(out.join("authors.json"))?,
+            first_post: true,
+            first_comment: true,
+        })
+    }
+}
+
+fn write_json(
+    writer: &mut impl Write,
+    first: &mut bool,
+    row: &impl Serialize,
+) -> std::io::Result<()> {
+    writer.write_all(if *first { b"[\n" } else { b",\n" })?;
+    *first = false;
+    serde_json::to_writer(&mut *writer, row)?;
+    Ok(())
+}
+
+fn finish_json(writer: &mut impl Write, first: bool) -> std::io::Result<()> {
+    writer.write_all(if first { b"[]\n" } else { b"\n]\n" })?;
+    writer.flush()
+}
+
+impl Sink for JsonSink {
+    fn thread(&mut self, thread: &Thread, revision: &Revision) -> std::io::Result<()> {
+        write_json(
+            &mut self.posts,
+            &mut self.first_post,
+            &post_row(thread, revision),
+        )?;
+        for comment in &revision.comments {
+            write_json(
+                &mut self.comments,
+                &mut self.first_comment,
+                &comment_row(thread, comment),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, authors: Vec<AuthorRow>) -> std::io::Result<()> {
+        finish_json(&mut self.posts, self.first_post)?;
+        finish_json(&mut self.comments, self.first_comment)?;
+        serde_json::to_writer_pretty(&mut self.authors, &authors)?;
+        self.authors.write_all(b"\n")?;
+        self.authors.flush()
+    }
+}
+
+/// Schreibt `posts.csv`, `comments.csv` und `authors.csv`, jeweils mit einer Kopfzeile
+struct CsvSink {
+    posts: BufWriter<File>,
+    comments: BufWriter<File>,
+    authors: BufWriter<File>,
+}
+
+impl CsvSink {
+    fn new(out: &Path) -> Result<Self, String> {
+        let mut sink = CsvSink {
+            posts: create(out.join("posts.csv"))?,
+            comments: create(out.join("comments.csv"))?,
+            authors: create(out.join("authors.csv"))?,
+        };
+
+        write_csv_header(&mut sink.posts, &PostRow::default())
+            .and_then(|_| write_csv_header(&mut sink.comments, &CommentRow::default()))
+            .and_then(|_| write_csv_header(&mut sink.authors, &AuthorRow::default()))
+            .map_err(|err| format!("Fehler beim Schreiben des Exports: {}", err))?;
+
+        Ok(sink)
+    }
+}
+
+/// Die Spalten einer Zeile mit ihren Werten. Kopfzeile und Zeilen kommen beide hierher, deshalb
+/// passen die Spalten immer zusammen.
+fn csv_fields(row: &impl Serialize) -> std::io::Result<serde_json::Map<String, serde_json::Value>> {
+    match serde_json::to_value(row)? {
+        serde_json::Value::Object(fields) => Ok(fields),
+        other => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Keine Zeile mit Spalten: {}", other),
+        )),
+    }
+}
+
+/// Schreibt die Namen der Spalten von `row` als Kopfzeile
+fn write_csv_header(writer: &mut impl Write, row: &impl Serialize) -> std::io::Result<()> {
+    let names: Vec<String> = csv_fields(row)?
+        .keys()
+        .map(|name| csv_escape(name))
+        .collect();
+    writeln!(writer, "{}", names.join(","))
+}
+
+/// Schreibt ein Objekt als CSV-Zeile
+fn write_csv(writer: &mut impl Write, row: &impl Serialize) -> std::io::Result<()> {
+    let line: Vec<String> = csv_fields(row)?
+        .values()
+        .map(|value| match value {
+            serde_json::Value::Null => String::new(),
+            serde_json::Value::String(string) => csv_escape(string),
+            other => other.to_string(),
+        })
+        .collect();
+    writeln!(writer, "{}", line.join(","))
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl Sink for CsvSink {
+    fn thread(&mut self, thread: &Thread, revision: &Revision) -> std::io::Result<()> {
+        write_csv(&mut self.posts, &post_row(thread, revision))?;
+        for comment in &revision.comments {
+            write_csv(&mut self.comments, &comment_row(thread, comment))?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, authors: Vec<AuthorRow>) -> std::io::Result<()> {
+        for author in &authors {
+            write_csv(&mut self.authors, author)?;
+        }
+        self.posts.flush()?;
+        self.comments.flush()?;
+        self.authors.flush()
+    }
+}
+
+/// Schreibt für jeden Thread eine Datei `<instanz>/<post id>.md`, dazu `index.md` mit allen Posts
+/// und `authors.md`
+struct MarkdownSink {
+    out: PathBuf,
+    index: BufWriter<File>,
+}
+
+impl MarkdownSink {
+    fn new(out: &Path) -> Result<Self, String> {
+        let mut index = create(out.join("index.md"))?;
+        writeln!(index, "# Export\n\n| Instanz | Post | Community | Autor | Score | Veröffentlicht |\n|---|---|---|---|---|---|")
+            .map_err(|err| format!("Fehler beim Schreiben des Exports: {}", err))?;
+        Ok(MarkdownSink {
+            out: out.to_path_buf(),
+            index,
+        })
+    }
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+impl Sink for MarkdownSink {
+    fn thread(&mut self, thread: &Thread, revision: &Revision) -> std::io::Result<()> {
+        let post = &revision.post;
+        let relative = format!("{}/{}.md", thread.instance, post.id);
+
+        writeln!(
+            self.index,
+            "| {} | [{}]({}) | {} | {} | {} | {} |",
+            thread.instance,
+            markdown_cell(&post.title),
+            relative,
+            markdown_cell(&post.community),
+            markdown_cell(&post.author),
+            post.score,
+            post.published.to_rfc3339()
+        )?;
+
+        create_dir_all(self.out.join(&thread.instance))?;
+        let mut file = BufWriter::new(File::create(self.out.join(&relative))?);
+
+        writeln!(file, "# {}\n", post.title)?;
+        if let Some(label) = removal_label(post.deleted, post.removed) {
+            writeln!(file, "**({})**\n", label)?;
+        }
+        writeln!(
+            file,
+            "*{}* von **{}**, Score {}, {}\n",
+            post.community,
+            post.author,
+            post.score,
+            post.published.to_rfc3339()
+        )?;
+        if let Some(url) = &post.url {
+            writeln!(file, "<{}>\n", url)?;
+        }
+        if let Some(body) = &post.body {
+            writeln!(file, "{}\n", body)?;
+        }
+
+        writeln!(file, "## Kommentare\n")?;
+        write_comment_tree(&mut file, revision, None, 0)?;
+        file.flush()
+    }
+
+    fn finish(&mut self, authors: Vec<AuthorRow>) -> std::io::Result<()> {
+        self.index.flush()?;
+
+        let mut file = BufWriter::new(File::create(self.out.join("authors.md"))?);
+        writeln!(
+            file,
+            "# Autoren\n\n| Autor | Posts | Kommentare | Zuerst gesehen | Zuletzt gesehen |\n|---|---|---|---|---|"
+        )?;
+        for author in authors {
+            writeln!(
+                file,
+                "| {} | {} | {} | {} | {} |",
+                markdown_cell(&author.author),
+                author.posts,
+                author.comments,
+                author.first_seen.to_rfc3339(),
+                author.last_seen.to_rfc3339()
+            )?;
+        }
+        file.flush()
+    }
+}
+
+/// Schreibt alle Antworten auf `parent` als verschachtelte Liste
+fn write_comment_tree(
+    file: &mut impl Write,
+    revision: &Revision,
+    parent: Option<i32>,
+    depth: usize,
+) -> std::io::Result<()> {
+    for comment in revision.replies(parent) {
+        let indent = "  ".repeat(depth);
+        let label = removal_label(comment.deleted, comment.removed)
+            .map(|label| format!(", {}", label))
+            .unwrap_or_default();
+        writeln!(
+            file,
+            "{}- **{}** ({}{}): {}",
+            indent,
+            comment.author,
+            comment.score,
+            label,
+            comment.body.replace('\n', &format!("\n{}  ", indent))
+        )?;
+        write_comment_tree(file, revision, Some(comment.id), depth + 1)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs::read_to_string, process};
+
+    use super::*;
+    use crate::archive::Post;
+
+    fn thread(id: i32, comments: usize) -> Thread {
+        let published = DateTime::default();
+        Thread {
+            instance: "feddit.org".to_string(),
+            id,
+            revisions: vec![Revision {
+                archived: Local::now(),
+                post: Post {
+                    id,
+                    community: "politik".to_string(),
+                    author: "jemand".to_string(),
+                    title: format!("Post {}", id),
+                    body: None,
+                    url: None,
+                    score: 1,
+                    published,
+                    nsfw: false,
+                    deleted: false,
+                    removed: false,
+                },
+                comments: (0..comments as i32)
+                    .map(|comment| Comment {
+                        id: id * 100 + comment,
+                        parent: None,
+                        author: "jemand".to_string(),
+                        body: "Ein Kommentar".to_string(),
+                        score: 1,
+                        published,
+                        deleted: false,
+                        removed: false,
+                    })
+                    .collect(),
+            }],
+        }
+    }
+
+    fn csv(write: impl FnOnce(&mut Vec<u8>) -> std::io::Result<()>) -> String {
+        let mut out = Vec::new();
+        write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv_quotes_special_characters() {
+        let thread = thread(1, 0);
+        let comment = Comment {
+            id: 7,
+            parent: Some(3),
+            author: "a,b".to_string(),
+            body: "Erstens, \"zweitens\"\ndrittens".to_string(),
+            score: -2,
+            published: DateTime::default(),
+            deleted: false,
+            removed: true,
+        };
+
+        let line = csv(|out| write_csv(out, &comment_row(&thread, &comment)));
+        assert_eq!(
+            line,
+            "feddit.org,1,7,3,\"a,b\",\"Erstens, \"\"zweitens\"\"\ndrittens\",-2,\
+             1970-01-01T00:00:00Z,false,true\n"
+        );
+    }
+
+    #[test]
+    fn csv_header_matches_rows() {
+        let thread = thread(1, 1);
+        let revision = thread.latest().unwrap();
+
+        let header = csv(|out| write_csv_header(out, &PostRow::default()));
+        let row = csv(|out| write_csv(out, &post_row(&thread, revision)));
+        assert!(header.starts_with("instance,post_id,community,author,title,body,url,"));
+        assert_eq!(header.split(',').count(), row.split(',').count());
+
+        let header = csv(|out| write_csv_header(out, &CommentRow::default()));
+        let row = csv(|out| write_csv(out, &comment_row(&thread, &revision.comments[0])));
+        assert!(header.starts_with("instance,post_id,comment_id,parent_id,"));
+        assert_eq!(header.split(',').count(), row.split(',').count());
+    }
+
+    #[test]
+    fn json_arrays_across_threads() {
+        let out = env::temp_dir().join(format!("feddit_archivieren_export_{}", process::id()));
+        create_dir_all(&out).unwrap();
+
+        let mut sink = JsonSink::new(&out).unwrap();
+        for (id, comments) in [(1, 2), (2, 0), (3, 1)] {
+            let thread = thread(id, comments);
+            sink.thread(&thread, thread.latest().unwrap()).unwrap();
+        }
+        sink.finish(Vec::new()).unwrap();
+
+        let read = |name: &str| -> Vec<serde_json::Value> {
+            serde_json::from_str(&read_to_string(out.join(name)).unwrap()).unwrap()
+        };
+        let posts = read("posts.json");
+        assert_eq!(posts.len(), 3);
+        assert_eq!(posts[2]["post_id"], 3);
+        assert_eq!(read("comments.json").len(), 3);
+        assert!(read("authors.json").is_empty());
+
+        // Ohne Threads sind es leere Arrays
+        let mut sink = JsonSink::new(&out).unwrap();
+        sink.finish(Vec::new()).unwrap();
+        assert!(read("posts.json").is_empty());
+        assert!(read("comments.json").is_empty());
+
+        std::fs::remove_dir_all(&out).unwrap();
+    }
+}
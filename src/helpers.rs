@@ -1,17 +1,25 @@
 #![allow(dead_code)]
 
 use std::{
+    cmp::Ordering,
     fs::{read_to_string, remove_dir_all, rename, File},
     io::{BufRead, BufReader, Read},
     net::TcpStream,
+    os::unix::process::CommandExt,
     path::Path,
-    process::{exit, Command, Output},
-    sync::{Arc, Mutex},
+    process::{exit, Command, Output, Stdio},
+    thread,
+    time::Duration,
 };
 
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
 use git2::Repository;
+use semver::Version;
 
-use crate::settings::{self, PID_FILE};
+use crate::{
+    config::{paths, user_mode, PathsConfig, UpdateConfig},
+    settings,
+};
 
 #[macro_export]
 macro_rules! trust_me_bro {
@@ -42,20 +50,20 @@ pub fn feddit_archivieren_assert(condition: bool, message: &str) {
     }
 }
 
-/// Returnt true wenn PID_FILE existiert, false wenn nicht
+/// Returnt true wenn das PID-File existiert, false wenn nicht
 pub fn pid_file_exists() -> bool {
-    Path::new(PID_FILE).exists()
+    Path::new(&paths().pid_file()).exists()
 }
 
-/// Returnt den Inhalt von PID_FILE, wenn es nicht existiert exitet mit 1
+/// Returnt den Inhalt des PID-Files, wenn es nicht existiert exitet mit 1
 pub fn read_pid_file() -> Result<String, String> {
     feddit_archivieren_assert(
         pid_file_exists(),
         "Versuche PID Datei zu lesen, sie existiert aber nicht.",
     );
-    match BufReader::new(File::open(PID_FILE).unwrap())
+    match BufReader::new(File::open(paths().pid_file()).unwrap())
         .lines()
-        .filter_map(Result::ok)
+        .map_while(Result::ok)
         .next()
     {
         Some(line) => Ok(line),
@@ -63,17 +71,21 @@ pub fn read_pid_file() -> Result<String, String> {
     }
 }
 
+/// Returnt ob der Daemon mit den Pfaden `paths` gerade läuft, z.B. der eines anderen Profils
+pub fn daemon_running_at(paths: &PathsConfig) -> bool {
+    let Ok(file) = File::open(paths.pid_file()) else {
+        return false;
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .next()
+        .is_some_and(|pid| !pid.is_empty() && Path::new(&format!("/proc/{}", pid)).exists())
+}
+
 /// Returnt ob der Daemon gerade läuft
 pub fn daemon_running() -> bool {
-    if pid_file_exists() {
-        if let Ok(pid) = read_pid_file() {
-            Path::new(&format!("/proc/{}", pid)).exists()
-        } else {
-            false
-        }
-    } else {
-        false
-    }
+    daemon_running_at(paths())
 }
 
 /// Ändert die Berechtigungen von `filepath` zu `mode`
@@ -86,6 +98,21 @@ pub fn root() -> bool {
     users::get_current_uid() == 0
 }
 
+/// Ob Befehle erlaubt sind die die Installation verändern. Im Benutzermodus gehört sie dem Nutzer,
+/// sonst braucht es root.
+pub fn privileged() -> bool {
+    root() || user_mode()
+}
+
+/// Das Target im Makefile mit dem installiert wird
+pub fn make_install_target() -> &'static str {
+    if user_mode() {
+        "install_user"
+    } else {
+        "install"
+    }
+}
+
 /// Führt einen Befehl aus und exitet mit einer Fehlermeldung sobald ein Fehler auftritt
 pub fn run_command(command: &mut Command) {
     match command.output() {
@@ -109,7 +136,7 @@ pub fn command_output_formater(output: &Output) -> String {
     let mut x = output
         .stdout
         .lines()
-        .filter_map(Result::ok)
+        .map_while(Result::ok)
         .filter(|line| !line.is_empty())
         .collect::<Vec<String>>()
         .join("\n");
@@ -117,7 +144,7 @@ pub fn command_output_formater(output: &Output) -> String {
         output
             .stderr
             .lines()
-            .filter_map(Result::ok)
+            .map_while(Result::ok)
             .filter(|line| !line.is_empty())
             .collect::<Vec<String>>()
             .join("\n")
@@ -143,6 +170,72 @@ pub fn get(filepath: &str) -> String {
     }
 }
 
+/// Parst eine Zeitangabe wie `90s`, `10m`, `2h` oder `1d`. Eine Zahl ohne Einheit wird als Sekunden
+/// interpretiert.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("Ungültige Zeitangabe: \"{}\"", input))?;
+
+    let factor = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        "w" => 60 * 60 * 24 * 7,
+        _ => return Err(format!("Unbekannte Zeiteinheit: \"{}\"", unit)),
+    };
+
+    number
+        .checked_mul(factor)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("Zeitangabe zu groß: \"{}\"", input))
+}
+
+/// Der Zeitpunkt der `duration` vor jetzt liegt, `None` wenn er nicht darstellbar ist
+pub fn time_ago(duration: Duration) -> Option<DateTime<Local>> {
+    chrono::Duration::from_std(duration)
+        .ok()
+        .and_then(|duration| Local::now().checked_sub_signed(duration))
+}
+
+/// Parst einen Zeitpunkt, entweder als Datum (`2024-03-12`), als Datum mit Uhrzeit
+/// (`2024-03-12 14:30`) oder relativ zu jetzt (`7d`, siehe `parse_duration`)
+pub fn parse_time(input: &str) -> Result<DateTime<Local>, String> {
+    let input = input.trim();
+
+    if let Ok(duration) = parse_duration(input) {
+        return time_ago(duration).ok_or_else(|| format!("Zeitangabe zu groß: \"{}\"", input));
+    }
+
+    let naive = NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M").or_else(|_| {
+        NaiveDate::parse_from_str(input, "%Y-%m-%d")
+            .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default())
+    });
+
+    match naive.map(|naive| naive.and_local_timezone(Local).earliest()) {
+        Ok(Some(time)) => Ok(time),
+        _ => Err(format!(
+            "Ungültiger Zeitpunkt: \"{}\" (erwartet z.B. 2024-03-12, \"2024-03-12 14:30\" oder 7d)",
+            input
+        )),
+    }
+}
+
+/// Returnt den Host einer URL, also z.B. `feddit.de` für `https://feddit.de/c/dach`
+pub fn instance_of(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(url) => url.host_str().unwrap_or_default().to_string(),
+        Err(_) => url.to_string(),
+    }
+}
+
 /// Liest eine Nachricht aus einem TcpStream und returnt sie.
 pub fn read_from_stream(stream: &mut TcpStream) -> String {
     let mut buf = [0; settings::TCP_BUFFER_SIZE];
@@ -158,47 +251,114 @@ pub fn to_rust_string(buf: &[u8; settings::TCP_BUFFER_SIZE]) -> String {
     string.trim_end_matches('\0').into()
 }
 
-fn get_update_version() -> String {
-    let content = read_to_string(format!("{}/Cargo.toml", settings::UDPATE_DIR)).unwrap();
-    let toml: toml::Value = content.parse().unwrap();
-    toml.get("package")
+fn get_update_version() -> Result<Version, String> {
+    let path = format!("{}/Cargo.toml", paths().update_dir);
+    let content =
+        read_to_string(&path).map_err(|err| format!("Fehler beim Lesen von {}: {}", path, err))?;
+    let toml: toml::Value = content
+        .parse()
+        .map_err(|err| format!("Fehler beim Lesen von {}: {}", path, err))?;
+    let version = toml
+        .get("package")
         .and_then(|package| package.get("version"))
         .and_then(|version| version.as_str())
-        .unwrap()
-        .to_string()
+        .ok_or_else(|| format!("{} enthält keine Version.", path))?;
+    Version::parse(version).map_err(|err| format!("Ungültige Version {}: {}", version, err))
+}
+
+/// Die Version in einem Tag wie `v1.10.2`, falls er eine gültige Semver-Version ist
+fn tag_version(tag: &str) -> Option<Version> {
+    Version::parse(tag.strip_prefix('v').unwrap_or(tag)).ok()
+}
+
+/// Der Tag mit der höchsten Version der auf `pattern` passt, Versionen aus `skip` zählen nicht.
+fn newest_tag(repo: &Repository, pattern: &str, skip: &[Version]) -> Result<String, String> {
+    let tags = repo
+        .tag_names(Some(pattern))
+        .map_err(|err| format!("Fehler beim Lesen der Tags: {}", err))?;
+
+    newest(tags.iter().flatten(), skip)
+        .map(str::to_string)
+        .ok_or_else(|| format!("Kein Tag passt auf {}.", pattern))
+}
+
+/// Der Tag mit der höchsten Version, Versionen aus `skip` zählen nicht. Tags mit
+/// Semver-Versionen gehen vor allen anderen.
+fn newest<'a>(tags: impl IntoIterator<Item = &'a str>, skip: &[Version]) -> Option<&'a str> {
+    tags.into_iter()
+        .filter(|tag| !tag_version(tag).is_some_and(|version| skip.contains(&version)))
+        .max_by_key(|tag| (tag_version(tag), version_key(tag)))
+}
+
+/// Der Tag der genau die Version `pin` hat, z.B. `v0.0.46` oder `0.0.46`
+fn pinned_tag(repo: &Repository, pin: &Version) -> Result<String, String> {
+    let tags = repo
+        .tag_names(None)
+        .map_err(|err| format!("Fehler beim Lesen der Tags: {}", err))?;
+
+    tags.iter()
+        .flatten()
+        .find(|tag| tag_version(tag).as_ref() == Some(pin))
+        .map(str::to_string)
+        .ok_or_else(|| format!("Kein Tag hat die gepinnte Version {}.", pin))
+}
+
+/// Die Zahlen in einem Tag, z.B. `[1, 10, 2]` für `v1.10.2`, damit `v1.10` nach `v1.9` kommt
+fn version_key(tag: &str) -> Vec<u64> {
+    tag.split(|char: char| !char.is_ascii_digit())
+        .filter_map(|part| part.parse().ok())
+        .collect()
 }
 
-fn get_current_version() -> String {
-    env!("CARGO_PKG_VERSION").to_string()
+pub fn get_current_version() -> Version {
+    Version::parse(env!("CARGO_PKG_VERSION")).unwrap()
 }
 
-/// Updatet das Programm
-pub fn update(
-    print_override: Option<fn(message: &str, streams: Arc<Mutex<Vec<TcpStream>>>)>,
-    print_args: Option<Arc<Mutex<Vec<TcpStream>>>>,
-) -> Result<(), String> {
+/// Updatet das Programm aus dem Repository und dem Kanal in `source`, bzw. auf die gepinnte
+/// Version. Versionen aus `update.skip` werden nie installiert, ältere Versionen nur mit `force`.
+/// Returnt ob eine andere Version installiert wurde.
+///
+/// Mit `detach` wird `make install` nur gestartet und nicht abgewartet, siehe
+/// [`install_detached`]. `Ok(true)` heißt dann, dass die Installation läuft.
+pub fn update<T: Clone>(
+    source: &UpdateConfig,
+    force: bool,
+    detach: bool,
+    print_override: Option<fn(message: &str, args: T)>,
+    print_args: Option<T>,
+) -> Result<bool, String> {
     macro_rules! print_maybe_override {
         ($($e:expr), *) => {{
             let msg = &format!($($e), *);
-            if print_override.is_some() {
-                print_override.unwrap()(msg, print_args.clone().unwrap())
+            if let Some(print_override) = print_override {
+                print_override(msg, print_args.clone().unwrap())
             } else {
                 println!("{}", msg);
             }
         }};
     }
 
-    let old_dir = Path::new(settings::UDPATE_DIR);
+    let paths = paths();
+    let repository = &source.repository;
+    let channel = source.current_channel()?;
+    let current = get_current_version();
+
+    if source.pin.as_ref() == Some(&current) {
+        print_maybe_override!("Die gepinnte Version {} ist bereits installiert.", current);
+        return Ok(false);
+    }
+
+    let old_dir = Path::new(&paths.update_dir);
     let build_cache = old_dir.join("target");
     let mut build_cache_exists = build_cache.exists();
 
     if build_cache_exists {
-        if Path::new(settings::UDPATE_CACHE_DIR).exists() {
-            print_maybe_override!("Lösche {}...", settings::UDPATE_CACHE_DIR);
-            if let Err(err) = remove_dir_all(settings::UDPATE_CACHE_DIR) {
+        if Path::new(&paths.update_cache_dir).exists() {
+            print_maybe_override!("Lösche {}...", &paths.update_cache_dir);
+            if let Err(err) = remove_dir_all(&paths.update_cache_dir) {
                 print_maybe_override!(
                     "Fehler beim Löschen von {}: {}",
-                    settings::UDPATE_CACHE_DIR,
+                    &paths.update_cache_dir,
                     err
                 );
             }
@@ -207,14 +367,14 @@ pub fn update(
         print_maybe_override!(
             "Bewege {:?} nach \"{}\"...",
             build_cache,
-            settings::UDPATE_CACHE_DIR
+            &paths.update_cache_dir
         );
 
-        if let Err(err) = rename(&build_cache, settings::UDPATE_CACHE_DIR) {
+        if let Err(err) = rename(&build_cache, &paths.update_cache_dir) {
             print_maybe_override!(
                 "Fehler beim Bewegen des Caches von {:?} zu {}: {}.",
                 build_cache,
-                settings::UDPATE_CACHE_DIR,
+                &paths.update_cache_dir,
                 err
             );
 
@@ -224,79 +384,34 @@ pub fn update(
     }
 
     if old_dir.exists() {
-        print_maybe_override!("Lösche {}...", settings::UDPATE_DIR);
+        print_maybe_override!("Lösche {}...", &paths.update_dir);
 
-        if let Err(err) = remove_dir_all(settings::UDPATE_DIR) {
+        if let Err(err) = remove_dir_all(&paths.update_dir) {
             return Err(format!(
                 "Fehler beim Löschen von {}: {}",
-                settings::UDPATE_DIR,
-                err
+                &paths.update_dir, err
             ));
         }
     }
 
-    print_maybe_override!(
-        "Klone {} nach {}...",
-        settings::GITHUB_LINK,
-        settings::UDPATE_DIR
-    );
+    print_maybe_override!("Klone {} nach {}...", repository, &paths.update_dir);
 
-    if let Err(err) = Repository::clone(settings::GITHUB_LINK, settings::UDPATE_DIR) {
-        return Err(format!("Fehler beim Klonen: {}", err));
+    let repo = match Repository::clone(repository, &paths.update_dir) {
+        Ok(repo) => repo,
+        Err(err) => return Err(format!("Fehler beim Klonen: {}", err)),
     };
 
-    if settings::GIT_BRANCH != "main" {
-        print_maybe_override!(
-            "Wechsel von Branch main zu Branch {}...",
-            settings::GIT_BRANCH
-        );
-        let success;
-        match Command::new("git")
-            .arg("checkout")
-            .arg(settings::GIT_BRANCH)
-            .current_dir(settings::UDPATE_DIR)
-            .output()
-        {
-            Ok(output) => {
-                success = output.status.success();
-                if output.status.success() {
-                    print_maybe_override!("Zu Branch {} gewechselt.", settings::GIT_BRANCH);
-                } else {
-                    print_maybe_override!(
-                        "Fehler beim Auschecken von {} in {}: {}",
-                        settings::GIT_BRANCH,
-                        settings::UDPATE_DIR,
-                        command_output_formater(&output)
-                    );
-                }
-            }
-            Err(err) => {
-                success = false;
-                print_maybe_override!(
-                    "Fehler beim Auschecken von {} in {}: {}",
-                    settings::GIT_BRANCH,
-                    settings::UDPATE_DIR,
-                    err
-                );
-            }
-        }
-
-        if !success {
-            print_maybe_override!("Falle auf main zurück.");
-        }
-    }
-
     if build_cache_exists {
         print_maybe_override!(
             "Bewege \"{}\" nach {:?}...",
-            settings::UDPATE_CACHE_DIR,
+            &paths.update_cache_dir,
             build_cache
         );
 
-        if let Err(err) = rename(settings::UDPATE_CACHE_DIR, &build_cache) {
+        if let Err(err) = rename(&paths.update_cache_dir, &build_cache) {
             print_maybe_override!(
                 "Fehler beim Bewegen des Caches von {} zu {:?}: {}.",
-                settings::UDPATE_CACHE_DIR,
+                &paths.update_cache_dir,
                 build_cache,
                 err
             );
@@ -305,26 +420,85 @@ pub fn update(
         }
     }
 
+    // Der Klon steht auf dem Standardbranch, ausgecheckt wird was der Kanal vorgibt
+    let target = match (&source.pin, &channel.branch, &channel.tag) {
+        (Some(pin), _, _) => pinned_tag(&repo, pin)?,
+        (None, Some(branch), _) => branch.clone(),
+        (None, None, Some(pattern)) => newest_tag(&repo, pattern, &source.skip)?,
+        (None, None, None) => return Err(format!("Der Kanal {} hat kein Ziel.", source.channel)),
+    };
+    match &source.pin {
+        Some(pin) => print_maybe_override!("Gepinnt auf {}, checke {} aus...", pin, target),
+        None => print_maybe_override!(
+            "Folge Kanal {} ({}), checke {} aus...",
+            source.channel,
+            channel,
+            target
+        ),
+    }
+    match Command::new("git")
+        .arg("checkout")
+        .arg(&target)
+        .current_dir(&paths.update_dir)
+        .output()
+    {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => {
+            return Err(format!(
+                "Fehler beim Auschecken von {} in {}: {}",
+                target,
+                &paths.update_dir,
+                command_output_formater(&output)
+            ))
+        }
+        Err(err) => {
+            return Err(format!(
+                "Fehler beim Auschecken von {} in {}: {}",
+                target, &paths.update_dir, err
+            ))
+        }
+    }
+
     print_maybe_override!("Fertig!");
 
-    if get_current_version() == get_update_version() {
-        print_maybe_override!("Bereits die neuste Version ({}).", get_current_version());
-        return Ok(());
+    let found = get_update_version()?;
+    if source.skip.contains(&found) {
+        print_maybe_override!("Version {} steht in update.skip, überspringe sie.", found);
+        return Ok(false);
+    }
+    match found.cmp(&current) {
+        Ordering::Equal => {
+            print_maybe_override!("Bereits die neuste Version ({}).", current);
+            return Ok(false);
+        }
+        Ordering::Less if !force => {
+            return Err(format!(
+                "Die gefundene Version {} ist älter als die installierte {}, ein Downgrade geht \
+                 nur mit --force.",
+                found, current
+            ))
+        }
+        Ordering::Less => print_maybe_override!("Downgrade: {} -> {}", current, found),
+        Ordering::Greater => {
+            print_maybe_override!("Neue Version gefunden: {} -> {}", current, found)
+        }
     }
-
-    print_maybe_override!(
-        "Neue Version gefunden: {} -> {}",
-        get_current_version(),
-        get_update_version()
-    );
     print_maybe_override!("Compile den Source Code...");
 
     // Den Code mithilfe des Makefiles compilen und installieren
-    match Command::new("make")
-        .current_dir(settings::UDPATE_DIR)
-        .arg("install")
-        .output()
-    {
+    let mut make = Command::new("make");
+    make.current_dir(&paths.update_dir)
+        .arg(make_install_target());
+    if detach {
+        install_detached(make)?;
+        print_maybe_override!(
+            "Installation von Version {} gestartet, siehe {}.",
+            found,
+            paths.update_log_file()
+        );
+        return Ok(true);
+    }
+    match make.output() {
         Ok(output) => {
             if !output.status.success() {
                 return Err(format!(
@@ -339,10 +513,113 @@ pub fn update(
     }
 
     print_maybe_override!("Fertig!");
-    print_maybe_override!(
-        "Die neuste Version ({}) ist jetzt installiert.",
-        get_update_version()
-    );
+    print_maybe_override!("Version {} ist jetzt installiert.", found);
     print_maybe_override!("Update erfolgreich abgeschlossen.");
+    Ok(true)
+}
+
+/// Startet die Installation in einer eigenen Session, ohne auf sie zu warten. Das braucht der
+/// Daemon: `client install` beendet ihn und muss ihn überleben, um den neuen Daemon zu starten,
+/// zu beobachten und wenn nötig zurückzurollen. Mit Pipes zum Daemon würde die Installation nach
+/// dessen Ende beim nächsten Schreiben an SIGPIPE sterben, deshalb geht die Ausgabe in
+/// `update_log_file`.
+#[allow(clippy::macro_metavars_in_unsafe)]
+fn install_detached(mut command: Command) -> Result<(), String> {
+    let log_file = paths().update_log_file();
+    let open_log = || {
+        File::options()
+            .create(true)
+            .append(true)
+            .open(&log_file)
+            .map_err(|err| format!("Fehler beim Öffnen von {}: {}", log_file, err))
+    };
+    command
+        .stdin(Stdio::null())
+        .stdout(open_log()?)
+        .stderr(open_log()?);
+    trust_me_bro! {
+        command.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|err| format!("Fehler bei der Installation: {}", err))?;
+    // Solange dieser Prozess lebt wird das Kind hier eingesammelt, danach von init
+    thread::spawn(move || child.wait());
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("10m"), Ok(Duration::from_secs(600)));
+        assert_eq!(parse_duration(" 2h "), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86400)));
+        assert_eq!(parse_duration("1w"), Ok(Duration::from_secs(604800)));
+    }
+
+    #[test]
+    fn parse_duration_rejects_invalid() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("5y").is_err());
+        assert!(parse_duration("-5s").is_err());
+    }
+
+    #[test]
+    fn parse_duration_rejects_overflow() {
+        assert!(parse_duration("99999999999999999w").is_err());
+    }
+
+    #[test]
+    fn tag_version_with_and_without_prefix() {
+        assert_eq!(tag_version("v1.10.2"), Some(Version::new(1, 10, 2)));
+        assert_eq!(tag_version("0.0.46"), Some(Version::new(0, 0, 46)));
+        assert_eq!(
+            tag_version("v1.0.0-rc.1"),
+            Some(Version::parse("1.0.0-rc.1").unwrap())
+        );
+        assert_eq!(tag_version("v1.10"), None);
+        assert_eq!(tag_version("nightly"), None);
+    }
+
+    #[test]
+    fn newest_compares_semver() {
+        assert_eq!(
+            newest(["v0.0.9", "v0.0.10", "v0.0.2"], &[]),
+            Some("v0.0.10")
+        );
+        assert_eq!(newest(["v1.0.0", "v1.0.0-rc.1"], &[]), Some("v1.0.0"));
+        assert_eq!(newest(["v0.1.0", "0.2.0"], &[]), Some("0.2.0"));
+    }
+
+    #[test]
+    fn newest_prefers_semver_tags() {
+        assert_eq!(newest(["v9.9", "v0.0.1", "nightly"], &[]), Some("v0.0.1"));
+        // Ohne Semver-Tags zählen die Zahlen im Tag
+        assert_eq!(newest(["v1.9", "v1.10", "nightly"], &[]), Some("v1.10"));
+    }
+
+    #[test]
+    fn newest_skips_versions() {
+        let skip = [Version::new(0, 0, 47)];
+        assert_eq!(newest(["v0.0.46", "v0.0.47"], &skip), Some("v0.0.46"));
+        assert_eq!(newest(["v0.0.47"], &skip), None);
+        assert_eq!(newest([], &skip), None);
+    }
+
+    #[test]
+    fn time_ago_out_of_range() {
+        assert!(time_ago(Duration::from_secs(60)).is_some());
+        assert!(time_ago(Duration::from_secs(u64::MAX)).is_none());
+    }
+}
@@ -0,0 +1,537 @@
+#![allow(dead_code)]
+
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, remove_file, rename, File},
+    io::{copy, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex, PoisonError,
+    },
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use reqwest::{blocking::Client, Url};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+
+use crate::{
+    archive::{Comment, Post},
+    config::paths,
+    helpers::instance_of,
+    settings,
+};
+
+/// Wie viele Kommentare pro Anfrage geholt werden, mehr erlaubt Lemmy nicht
+const COMMENT_PAGE_SIZE: usize = 50;
+/// Wie viele Posts eine Seite des Crawlers hat
+const POST_PAGE_SIZE: usize = 20;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// `crawl.requests_per_minute`, wird vom Daemon beim Start und beim Neuladen gesetzt
+static REQUESTS_PER_MINUTE: AtomicU32 = AtomicU32::new(settings::REQUESTS_PER_MINUTE);
+/// Wann die nächste Anfrage an eine Instanz frühestens geschickt werden darf
+static NEXT_REQUEST: Mutex<BTreeMap<String, Instant>> = Mutex::new(BTreeMap::new());
+
+/// Setzt wie viele Anfragen höchstens pro Minute an eine Instanz gehen
+pub fn set_requests_per_minute(requests: u32) {
+    REQUESTS_PER_MINUTE.store(requests.max(1), Ordering::SeqCst);
+}
+
+#[derive(Deserialize)]
+struct ResolveObjectResponse {
+    post: Option<PostView>,
+    comment: Option<CommentView>,
+}
+
+#[derive(Deserialize)]
+struct GetPostResponse {
+    post_view: PostView,
+}
+
+#[derive(Deserialize)]
+struct GetPostsResponse {
+    posts: Vec<PostView>,
+}
+
+#[derive(Deserialize)]
+struct GetSiteResponse {
+    site_view: SiteView,
+}
+
+#[derive(Deserialize)]
+struct SiteView {
+    local_site_rate_limit: Option<RateLimit>,
+}
+
+/// Das Limit für normale Anfragen: `message` Anfragen alle `message_per_second` Sekunden. Neuere
+/// Lemmy Versionen nennen die Felder anders.
+#[derive(Deserialize)]
+struct RateLimit {
+    #[serde(alias = "message_max_requests")]
+    message: u32,
+    #[serde(alias = "message_interval_seconds")]
+    message_per_second: u32,
+}
+
+impl RateLimit {
+    fn per_minute(&self) -> Option<u32> {
+        if self.message_per_second == 0 {
+            return None;
+        }
+        Some((u64::from(self.message) * 60 / u64::from(self.message_per_second)) as u32)
+    }
+}
+
+#[derive(Deserialize)]
+struct GetCommentsResponse {
+    comments: Vec<CommentView>,
+}
+
+#[derive(Deserialize)]
+struct PostView {
+    post: ApiPost,
+    creator: ApiPerson,
+    community: ApiCommunity,
+    counts: Counts,
+}
+
+#[derive(Deserialize)]
+struct CommentView {
+    comment: ApiComment,
+    creator: ApiPerson,
+    counts: Counts,
+}
+
+#[derive(Deserialize)]
+struct ApiPost {
+    id: i32,
+    name: String,
+    url: Option<String>,
+    body: Option<String>,
+    thumbnail_url: Option<String>,
+    #[serde(deserialize_with = "deserialize_time")]
+    published: DateTime<Utc>,
+    #[serde(default)]
+    nsfw: bool,
+    #[serde(default)]
+    deleted: bool,
+    #[serde(default)]
+    removed: bool,
+}
+
+#[derive(Deserialize)]
+struct ApiComment {
+    id: i32,
+    post_id: i32,
+    content: String,
+    /// Die IDs aller Elternkommentare, z.B. `0.12.34` für Kommentar 34 als Antwort auf 12
+    path: String,
+    #[serde(deserialize_with = "deserialize_time")]
+    published: DateTime<Utc>,
+    #[serde(default)]
+    deleted: bool,
+    #[serde(default)]
+    removed: bool,
+}
+
+#[derive(Deserialize)]
+struct ApiPerson {
+    name: String,
+    actor_id: String,
+}
+
+#[derive(Deserialize)]
+struct ApiCommunity {
+    name: String,
+    actor_id: String,
+}
+
+#[derive(Deserialize)]
+struct Counts {
+    score: i64,
+}
+
+/// Ältere Lemmy Versionen schicken Zeitpunkte ohne Zeitzone, gemeint ist dann UTC
+fn deserialize_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    let time = String::deserialize(deserializer)?;
+    DateTime::parse_from_rfc3339(&time)
+        .map(|time| time.with_timezone(&Utc))
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(&time, "%Y-%m-%dT%H:%M:%S%.f").map(|time| time.and_utc())
+        })
+        .map_err(serde::de::Error::custom)
+}
+
+/// Eine Verbindung zur API einer Lemmy Instanz
+pub struct Lemmy {
+    instance: String,
+    client: Client,
+}
+
+impl Lemmy {
+    pub fn new(instance: &str) -> Result<Lemmy, String> {
+        let client = Client::builder()
+            .user_agent(concat!("feddit_archivieren/", env!("CARGO_PKG_VERSION")))
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|err| format!("Fehler beim Erstellen des HTTP Clients: {}", err))?;
+
+        Ok(Lemmy {
+            instance: instance.to_string(),
+            client,
+        })
+    }
+
+    /// Die Instanz auf der eine URL liegt
+    pub fn for_url(url: &str) -> Result<Lemmy, String> {
+        let parsed =
+            Url::parse(url).map_err(|err| format!("Ungültige URL \"{}\": {}", url, err))?;
+        if parsed.host_str().is_none() {
+            return Err(format!("Die URL \"{}\" hat keinen Host.", url));
+        }
+        Lemmy::new(&instance_of(url))
+    }
+
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+
+    /// Wartet bis wieder eine Anfrage an die Instanz geschickt werden darf, siehe
+    /// `set_requests_per_minute`
+    fn throttle(&self) {
+        let interval = Duration::from_secs(60) / REQUESTS_PER_MINUTE.load(Ordering::SeqCst);
+        let wait = {
+            let mut next = NEXT_REQUEST.lock().unwrap_or_else(PoisonError::into_inner);
+            let now = Instant::now();
+            let at = next
+                .get(&self.instance)
+                .copied()
+                .filter(|at| *at > now)
+                .unwrap_or(now);
+            next.insert(self.instance.clone(), at + interval);
+            at - now
+        };
+        sleep(wait);
+    }
+
+    fn get<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        query: &[(&str, String)],
+    ) -> Result<T, String> {
+        self.throttle();
+        let url = format!("https://{}/api/v3/{}", self.instance, endpoint);
+        let response = self
+            .client
+            .get(&url)
+            .query(query)
+            .send()
+            .map_err(|err| format!("Fehler bei der Anfrage an {}: {}", url, err))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().unwrap_or_default();
+            return Err(format!(
+                "{} hat mit {} geantwortet: {}",
+                url,
+                status,
+                body.trim()
+            ));
+        }
+
+        response
+            .json()
+            .map_err(|err| format!("Ungültige Antwort von {}: {}", url, err))
+    }
+
+    /// Wie viele normale Anfragen die Instanz pro Minute erlaubt, `None` wenn sie es nicht verrät
+    pub fn requests_per_minute(&self) -> Result<Option<u32>, String> {
+        let site: GetSiteResponse = self.get("site", &[])?;
+        Ok(site
+            .site_view
+            .local_site_rate_limit
+            .and_then(|limit| limit.per_minute()))
+    }
+
+    /// Findet die ID des Posts zu einer Post- oder Kommentar-URL
+    pub fn resolve(&self, url: &str) -> Result<i32, String> {
+        let resolved: ResolveObjectResponse =
+            self.get("resolve_object", &[("q", url.to_string())])?;
+
+        match (resolved.post, resolved.comment) {
+            (Some(post), _) => Ok(post.post.id),
+            (None, Some(comment)) => Ok(comment.comment.post_id),
+            (None, None) => Err(format!(
+                "\"{}\" ist weder ein Post noch ein Kommentar.",
+                url
+            )),
+        }
+    }
+
+    /// Holt einen Post. Zusätzlich werden die URLs der Medien des Posts zurückgegeben.
+    pub fn post(&self, id: i32) -> Result<(Post, Vec<String>), String> {
+        let response: GetPostResponse = self.get("post", &[("id", id.to_string())])?;
+        Ok(self.convert_post(response.post_view))
+    }
+
+    /// Holt die Seite `page` der Postliste eines Ziels aus `crawl.targets`. Das Ziel ist eine URL
+    /// der Weboberfläche wie `https://feddit.de/c/politik?listingType=All&sort=New`, Community,
+    /// `listingType` und `sort` werden übernommen.
+    pub fn posts(&self, target: &str, page: u32) -> Result<Vec<Post>, String> {
+        let mut query = listing_query(target)?;
+        query.push(("limit", POST_PAGE_SIZE.to_string()));
+        query.push(("page", page.to_string()));
+
+        let response: GetPostsResponse = self.get("post/list", &query)?;
+        Ok(response
+            .posts
+            .into_iter()
+            .map(|view| self.convert_post(view).0)
+            .collect())
+    }
+
+    fn convert_post(&self, view: PostView) -> (Post, Vec<String>) {
+        let media = [&view.post.url, &view.post.thumbnail_url]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect();
+
+        let post = Post {
+            id: view.post.id,
+            community: self.qualified(&view.community.name, &view.community.actor_id),
+            author: self.qualified(&view.creator.name, &view.creator.actor_id),
+            title: view.post.name,
+            body: view.post.body,
+            url: view.post.url,
+            score: view.counts.score,
+            published: view.post.published,
+            nsfw: view.post.nsfw,
+            deleted: view.post.deleted,
+            removed: view.post.removed,
+        };
+
+        (post, media)
+    }
+
+    /// Holt alle Kommentare eines Posts, sortiert nach ID
+    pub fn comments(&self, post_id: i32) -> Result<Vec<Comment>, String> {
+        let mut comments = Vec::new();
+
+        for page in 1.. {
+            let response: GetCommentsResponse = self.get(
+                "comment/list",
+                &[
+                    ("post_id", post_id.to_string()),
+                    ("type_", "All".to_string()),
+                    ("sort", "Old".to_string()),
+                    ("limit", COMMENT_PAGE_SIZE.to_string()),
+                    ("page", page.to_string()),
+                ],
+            )?;
+
+            let count = response.comments.len();
+            comments.extend(response.comments.into_iter().map(|view| {
+                Comment {
+                    id: view.comment.id,
+                    parent: view
+                        .comment
+                        .path
+                        .split('.')
+                        .rev()
+                        .nth(1)
+                        .and_then(|parent| parent.parse().ok())
+                        .filter(|parent| *parent != 0),
+                    author: self.qualified(&view.creator.name, &view.creator.actor_id),
+                    body: view.comment.content,
+                    score: view.counts.score,
+                    published: view.comment.published,
+                    deleted: view.comment.deleted,
+                    removed: view.comment.removed,
+                }
+            }));
+
+            if count < COMMENT_PAGE_SIZE {
+                break;
+            }
+        }
+
+        // Falls sich die Seiten während dem Holen verschoben haben
+        comments.sort_by_key(|comment| comment.id);
+        comments.dedup_by_key(|comment| comment.id);
+        Ok(comments)
+    }
+
+    /// Lädt ein Bild oder Video nach `<media_dir>/<instanz>/<post id>/`. Alles andere (z.B. verlinkte
+    /// Artikel) wird ignoriert, dann wird `Ok(None)` zurückgegeben. Dateien über `max_size` Bytes
+    /// sind ein Fehler.
+    pub fn download_media(
+        &self,
+        post_id: i32,
+        url: &str,
+        max_size: u64,
+    ) -> Result<Option<PathBuf>, String> {
+        let name = Url::parse(url)
+            .ok()
+            .and_then(|url| url.path_segments()?.next_back().map(str::to_string))
+            .filter(|name| !name.is_empty() && !name.starts_with('.'))
+            .unwrap_or_else(|| "media".to_string());
+        let dir = Path::new(&paths().media_dir())
+            .join(&self.instance)
+            .join(post_id.to_string());
+        let path = dir.join(&name);
+
+        if path.exists() {
+            return Ok(Some(path));
+        }
+
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .map_err(|err| format!("Fehler beim Laden von {}: {}", url, err))?;
+        if !response.status().is_success() {
+            return Err(format!("{} hat mit {} geantwortet", url, response.status()));
+        }
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        if !content_type.starts_with("image/") && !content_type.starts_with("video/") {
+            return Ok(None);
+        }
+
+        if let Some(length) = response
+            .content_length()
+            .filter(|&length| length > max_size)
+        {
+            return Err(format!(
+                "{} ist {} Bytes groß, crawl.max_media_size erlaubt {}",
+                url, length, max_size
+            ));
+        }
+
+        create_dir_all(&dir)
+            .map_err(|err| format!("Fehler beim Erstellen von {:?}: {}", dir, err))?;
+        // Erst in eine .part Datei, damit abgebrochene Downloads nicht als fertig gelten
+        let part = path.with_file_name(format!("{}.part", name));
+        if let Err(err) = copy_limited(response, &part, max_size) {
+            let _ = remove_file(&part);
+            return Err(format!("Fehler beim Laden von {}: {}", url, err));
+        }
+        rename(&part, &path)
+            .map_err(|err| format!("Fehler beim Bewegen von {:?}: {}", part, err))?;
+
+        Ok(Some(path))
+    }
+
+    /// Nutzer und Communities von anderen Instanzen bekommen die Instanz angehängt, z.B.
+    /// `linux@lemmy.ml`
+    fn qualified(&self, name: &str, actor_id: &str) -> String {
+        match instance_of(actor_id) {
+            host if host == self.instance || host.is_empty() => name.to_string(),
+            host => format!("{}@{}", name, host),
+        }
+    }
+}
+
+/// Die Parameter für `post/list` aus einer URL der Weboberfläche. `page` bestimmt der Crawler selbst.
+fn listing_query(target: &str) -> Result<Vec<(&'static str, String)>, String> {
+    let url = Url::parse(target).map_err(|err| format!("Ungültige URL \"{}\": {}", target, err))?;
+
+    let mut query = vec![("sort", "New".to_string())];
+    for (key, value) in url.query_pairs() {
+        match &*key {
+            "listingType" => query.push(("type_", value.to_string())),
+            "sort" => query[0].1 = value.to_string(),
+            _ => {}
+        }
+    }
+
+    let mut segments = url.path_segments().into_iter().flatten();
+    if let (Some("c"), Some(community)) = (segments.next(), segments.next()) {
+        query.push(("community_name", community.to_string()));
+    }
+
+    Ok(query)
+}
+
+/// Schreibt `reader` nach `path`, aber höchstens `max_size` Bytes. Die Content-Length kann fehlen
+/// oder lügen, deshalb wird auch beim Kopieren gezählt.
+fn copy_limited(reader: impl Read, path: &Path, max_size: u64) -> Result<u64, String> {
+    let mut file = File::create(path)
+        .map_err(|err| format!("Fehler beim Erstellen von {:?}: {}", path, err))?;
+    // Ein Byte mehr als erlaubt lesen, um zu merken ob es zu groß ist
+    let copied = copy(&mut reader.take(max_size + 1), &mut file)
+        .map_err(|err| format!("Fehler beim Schreiben von {:?}: {}", path, err))?;
+    if copied > max_size {
+        return Err(format!(
+            "Größer als crawl.max_media_size ({} Bytes)",
+            max_size
+        ));
+    }
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copy_limited_stops_at_max_size() {
+        let path = std::env::temp_dir().join(format!(
+            "feddit_archivieren_media_{}.part",
+            std::process::id()
+        ));
+
+        assert_eq!(copy_limited(&[1u8; 10][..], &path, 10), Ok(10));
+        assert_eq!(std::fs::read(&path).unwrap().len(), 10);
+        assert!(copy_limited(&[1u8; 11][..], &path, 10).is_err());
+        // Auch beim Abbruch wird nicht mehr als ein Byte zu viel geschrieben
+        assert_eq!(std::fs::read(&path).unwrap().len(), 11);
+
+        let _ = remove_file(&path);
+    }
+
+    #[test]
+    fn listing_query_of_front_page() {
+        assert_eq!(
+            listing_query(crate::settings::FEDDIT_LINK).unwrap(),
+            [("sort", "New".to_string()), ("type_", "Local".to_string())]
+        );
+    }
+
+    #[test]
+    fn rate_limit_per_minute() {
+        let limit: RateLimit =
+            serde_json::from_str(r#"{"message": 180, "message_per_second": 60}"#).unwrap();
+        assert_eq!(limit.per_minute(), Some(180));
+        let limit: RateLimit =
+            serde_json::from_str(r#"{"message_max_requests": 10, "message_interval_seconds": 1}"#)
+                .unwrap();
+        assert_eq!(limit.per_minute(), Some(600));
+        let limit: RateLimit =
+            serde_json::from_str(r#"{"message": 10, "message_per_second": 0}"#).unwrap();
+        assert_eq!(limit.per_minute(), None);
+    }
+
+    #[test]
+    fn listing_query_of_community() {
+        assert_eq!(
+            listing_query("https://feddit.org/c/politik?sort=Hot&page=3").unwrap(),
+            [
+                ("sort", "Hot".to_string()),
+                ("community_name", "politik".to_string())
+            ]
+        );
+        assert_eq!(
+            listing_query("https://feddit.org/").unwrap(),
+            [("sort", "New".to_string())]
+        );
+    }
+}
@@ -0,0 +1,188 @@
+#![allow(dead_code)]
+
+use std::{
+    fmt,
+    fs::{metadata, remove_file, rename},
+    io::ErrorKind,
+    path::PathBuf,
+};
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+/// Wie wichtig ein Logeintrag ist, sortiert von unwichtig nach wichtig
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Mit Padding, damit die Zeilen im Log untereinander stehen
+        let name = match self {
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        };
+        f.pad(name)
+    }
+}
+
+impl Level {
+    fn parse(name: &str) -> Option<Level> {
+        match name {
+            "INFO" => Some(Level::Info),
+            "WARN" => Some(Level::Warn),
+            "ERROR" => Some(Level::Error),
+            _ => None,
+        }
+    }
+}
+
+/// Eine Zeile im Log des Daemons, z.B.
+/// `2024-03-12 14:30:00.123 INFO  control: Nachricht: "ping"`
+#[derive(Clone, Serialize)]
+pub struct Entry {
+    pub time: DateTime<Local>,
+    pub level: Level,
+    /// Der Teil des Daemons von dem der Eintrag kommt, z.B. `control` oder `updater`
+    pub component: String,
+    pub message: String,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:<5} {}: {}",
+            self.time.format(TIME_FORMAT),
+            self.level,
+            self.component,
+            self.message
+        )
+    }
+}
+
+impl Entry {
+    pub fn now(level: Level, component: &str, message: &str) -> Entry {
+        Entry {
+            time: Local::now(),
+            level,
+            component: component.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Parst eine Zeile aus dem Log. Zeilen die nicht im Format des Daemons sind (z.B. von einer
+    /// Panic) ergeben `None`.
+    pub fn parse(line: &str) -> Option<Entry> {
+        // Das Datum und die Uhrzeit enthalten selbst ein Leerzeichen
+        let mut parts = line.splitn(4, ' ');
+        let time = format!("{} {}", parts.next()?, parts.next()?);
+        let time = NaiveDateTime::parse_from_str(&time, TIME_FORMAT).ok()?;
+        let time = Local.from_local_datetime(&time).earliest()?;
+
+        let level = Level::parse(parts.next()?)?;
+        let (component, message) = parts.next()?.trim_start().split_once(": ")?;
+
+        Some(Entry {
+            time,
+            level,
+            component: component.to_string(),
+            message: message.to_string(),
+        })
+    }
+}
+
+/// Der Pfad der `number`-ten rotierten Version einer Logdatei, 0 ist die aktuelle Datei
+pub fn rotated_path(path: &str, number: usize) -> PathBuf {
+    match number {
+        0 => PathBuf::from(path),
+        number => PathBuf::from(format!("{}.{}", path, number)),
+    }
+}
+
+/// Schiebt eine Logdatei eine Version weiter (`daemon.out` wird zu `daemon.out.1` usw.), damit
+/// das Log des letzten Laufs nicht überschrieben wird. Es werden so viele alte Versionen
+/// behalten wie in `log.rotations` konfiguriert.
+pub fn rotate(path: &str) -> Result<(), String> {
+    match metadata(path) {
+        Ok(metadata) if metadata.len() > 0 => {}
+        Ok(_) => return Ok(()),
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(format!("Fehler beim Lesen von {}: {}", path, err)),
+    }
+
+    let oldest = rotated_path(path, config::current().log.rotations);
+    if let Err(err) = remove_file(&oldest) {
+        if err.kind() != ErrorKind::NotFound {
+            return Err(format!("Fehler beim Löschen von {:?}: {}", oldest, err));
+        }
+    }
+
+    for number in (0..config::current().log.rotations).rev() {
+        let from = rotated_path(path, number);
+        let to = rotated_path(path, number + 1);
+        match rename(&from, &to) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(format!("Fehler beim Bewegen von {:?}: {}", from, err)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Alle existierenden Versionen einer Logdatei, die älteste zuerst
+pub fn files(path: &str) -> Vec<PathBuf> {
+    (0..=config::current().log.rotations)
+        .rev()
+        .map(|number| rotated_path(path, number))
+        .filter(|path| path.exists())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_line() {
+        let entry =
+            Entry::parse(r#"2024-03-12 14:30:00.123 INFO  control: Nachricht: "ping""#).unwrap();
+        assert_eq!(
+            entry.time.naive_local().to_string(),
+            "2024-03-12 14:30:00.123"
+        );
+        assert!(entry.level == Level::Info);
+        assert_eq!(entry.component, "control");
+        // Nur das erste ": " trennt die Komponente ab
+        assert_eq!(entry.message, r#"Nachricht: "ping""#);
+    }
+
+    #[test]
+    fn parse_what_display_writes() {
+        for level in [Level::Info, Level::Warn, Level::Error] {
+            let line = Entry::now(level, "updater", "Update...").to_string();
+            let entry = Entry::parse(&line).unwrap();
+            assert_eq!(entry.to_string(), line);
+        }
+    }
+
+    #[test]
+    fn parse_foreign_lines() {
+        assert!(Entry::parse("thread 'main' panicked at src/daemon.rs:42:5:").is_none());
+        assert!(Entry::parse("2024-03-12 14:30:00.123 DEBUG control: x").is_none());
+        assert!(Entry::parse("2024-03-12 14:30:00.123 INFO  ohne Komponente").is_none());
+        assert!(Entry::parse("2024-03-12 INFO  control: x").is_none());
+        assert!(Entry::parse("").is_none());
+    }
+}
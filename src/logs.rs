@@ -0,0 +1,227 @@
+use std::{
+    fs::{metadata, File, Metadata},
+    io::{BufRead, BufReader},
+    os::unix::fs::MetadataExt,
+    path::Path,
+    thread::sleep,
+    time::Duration,
+};
+
+use chrono::{DateTime, Local};
+use colored::Colorize;
+use regex::Regex;
+use serde_json::json;
+
+use crate::{
+    config::paths,
+    log::{self, Entry, Level},
+    output,
+};
+
+/// Wie oft bei `--follow` nach neuen Zeilen geschaut wird
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Welche Logeinträge angezeigt werden
+pub struct Filter {
+    /// Nur Einträge mit mindestens diesem Level
+    pub level: Option<Level>,
+    pub component: Option<String>,
+    pub since: Option<DateTime<Local>>,
+    /// Wird auf die Nachricht angewendet
+    pub grep: Option<Regex>,
+}
+
+impl Filter {
+    fn matches(&self, entry: &Entry) -> bool {
+        self.level.map_or(true, |level| entry.level >= level)
+            && self
+                .component
+                .as_ref()
+                .map_or(true, |component| entry.component == *component)
+            && self.since.map_or(true, |since| entry.time >= since)
+            && self
+                .grep
+                .as_ref()
+                .map_or(true, |grep| grep.is_match(&entry.message))
+    }
+}
+
+/// Liest eine Logdatei und merkt sich wie weit, damit danach nur noch neue Zeilen gelesen werden.
+/// Wird die Datei beim Neustart des Daemons rotiert, wird die alte Datei zu Ende gelesen und dann
+/// die neue geöffnet.
+struct Tail {
+    path: String,
+    /// Das Level für Zeilen die nicht vom Daemon formatiert wurden, z.B. von einer Panic
+    level: Level,
+    reader: Option<BufReader<File>>,
+    inode: u64,
+    position: u64,
+    /// Eine Zeile die noch nicht zu Ende geschrieben wurde
+    partial: String,
+    last: Option<Entry>,
+    /// Der Zeitpunkt für unformatierte Zeilen am Anfang der Datei
+    modified: DateTime<Local>,
+}
+
+impl Tail {
+    fn new(path: String, level: Level) -> Tail {
+        Tail {
+            path,
+            level,
+            reader: None,
+            inode: 0,
+            position: 0,
+            partial: String::new(),
+            last: None,
+            modified: Local::now(),
+        }
+    }
+
+    /// Liest alle seit dem letzten Aufruf dazugekommenen Einträge
+    fn read_new(&mut self) -> Vec<Entry> {
+        let mut entries = Vec::new();
+
+        loop {
+            if self.reader.is_none() {
+                let Ok(file) = File::open(&self.path) else {
+                    return entries;
+                };
+                if let Ok(metadata) = file.metadata() {
+                    self.inode = metadata.ino();
+                    self.modified = modified(&metadata);
+                }
+                self.position = 0;
+                self.partial.clear();
+                self.reader = Some(BufReader::new(file));
+            }
+
+            let reader = self.reader.as_mut().unwrap();
+            let mut line = String::new();
+            while let Ok(read) = reader.read_line(&mut line) {
+                if read == 0 {
+                    break;
+                }
+                self.position += read as u64;
+
+                if !line.ends_with('\n') {
+                    self.partial.push_str(&line);
+                    break;
+                }
+                let complete = std::mem::take(&mut self.partial) + line.trim_end_matches('\n');
+                let entry = parse_line(&complete, self.last.as_ref(), self.modified, self.level);
+                self.last = Some(entry.clone());
+                entries.push(entry);
+                line.clear();
+            }
+
+            // Erst wenn die alte Datei zu Ende gelesen ist, wird zur neuen gewechselt
+            match metadata(&self.path) {
+                Ok(metadata) if metadata.ino() != self.inode || metadata.len() < self.position => {
+                    self.reader = None
+                }
+                _ => return entries,
+            }
+        }
+    }
+}
+
+/// Parst eine Zeile aus dem Log. Zeilen die nicht vom Daemon formatiert wurden gehören zum
+/// vorherigen Eintrag `last` und bekommen dessen Zeit und Komponente, gibt es keinen wird
+/// `modified` verwendet.
+fn parse_line(line: &str, last: Option<&Entry>, modified: DateTime<Local>, level: Level) -> Entry {
+    Entry::parse(line).unwrap_or_else(|| Entry {
+        time: last.map_or(modified, |last| last.time),
+        level,
+        component: last.map_or("daemon".to_string(), |last| last.component.clone()),
+        message: line.to_string(),
+    })
+}
+
+/// Liest eine rotierte Logdatei komplett
+fn read_file(path: &Path, level: Level) -> Result<Vec<Entry>, String> {
+    let file =
+        File::open(path).map_err(|err| format!("Fehler beim Lesen von {:?}: {}", path, err))?;
+
+    let modified = file
+        .metadata()
+        .map_or_else(|_| Local::now(), |metadata| modified(&metadata));
+
+    let mut entries: Vec<Entry> = Vec::new();
+    for line in BufReader::new(file).lines().map_while(Result::ok) {
+        entries.push(parse_line(&line, entries.last(), modified, level));
+    }
+    Ok(entries)
+}
+
+fn modified(metadata: &Metadata) -> DateTime<Local> {
+    metadata
+        .modified()
+        .map_or_else(|_| Local::now(), DateTime::<Local>::from)
+}
+
+/// Printet alle Logeinträge die zum Filter passen, aus den Logs des Daemons und ihren rotierten
+/// Versionen. Mit `follow` wird danach auf neue Einträge gewartet, auch über Neustarts des Daemons
+/// hinweg.
+pub fn logs(filter: &Filter, follow: bool) -> Result<(), String> {
+    let mut tails = [
+        Tail::new(paths().out_file(), Level::Info),
+        Tail::new(paths().err_file(), Level::Error),
+    ];
+
+    let mut entries = Vec::new();
+    for tail in &mut tails {
+        // Die aktuelle Datei liest der Tail selbst, damit er später dort weitermachen kann
+        for path in log::files(&tail.path)
+            .iter()
+            .filter(|path| *path != Path::new(&tail.path))
+        {
+            entries.extend(read_file(path, tail.level)?);
+        }
+        entries.extend(tail.read_new());
+    }
+
+    // Mit --json gibt es ohne --follow ein einzelnes Ergebnis, mit --follow eine Zeile pro Eintrag
+    if output::json() && !follow {
+        entries.sort_by_key(|entry| entry.time);
+        entries.retain(|entry| filter.matches(entry));
+        output::done(json!({ "entries": entries }));
+        return Ok(());
+    }
+
+    print_entries(entries, filter);
+    if !follow {
+        return Ok(());
+    }
+
+    loop {
+        sleep(FOLLOW_INTERVAL);
+        let entries = tails.iter_mut().flat_map(Tail::read_new).collect();
+        print_entries(entries, filter);
+    }
+}
+
+/// Sortiert die Einträge nach Zeit und printet die passenden
+fn print_entries(mut entries: Vec<Entry>, filter: &Filter) {
+    entries.sort_by_key(|entry| entry.time);
+
+    for entry in entries.iter().filter(|entry| filter.matches(entry)) {
+        if output::json() {
+            output::line(entry);
+            continue;
+        }
+
+        let level = format!("{:<5}", entry.level);
+        let level = match entry.level {
+            Level::Info => level.normal(),
+            Level::Warn => level.yellow(),
+            Level::Error => level.red(),
+        };
+        println!(
+            "{} {} {}: {}",
+            entry.time.format("%Y-%m-%d %H:%M:%S").to_string().dimmed(),
+            level,
+            entry.component.bold(),
+            entry.message
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use std::{
+    process::exit,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use serde::Serialize;
+use serde_json::json;
+
+/// Ob mit `--json` gestartet wurde
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Printet eine Nachricht für Menschen, mit `--json` wird sie verschluckt
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if !$crate::output::json() {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Wie `say!`, nur nach stderr, für Fehler und Warnungen nach denen es trotzdem weitergeht
+#[macro_export]
+macro_rules! say_err {
+    ($($arg:tt)*) => {
+        if !$crate::output::json() {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Die Fehlercodes für `--json`. Die Namen sind Teil der Schnittstelle und dürfen sich nicht
+/// ändern, neue Codes dürfen dazukommen.
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Der Befehl braucht einen laufenden Daemon
+    DaemonNotRunning,
+    /// Der Befehl geht nicht, während der Daemon läuft
+    DaemonRunning,
+    /// Der Daemon hat nicht oder nicht wie erwartet geantwortet
+    DaemonUnhealthy,
+    /// Der Daemon hat die Anfrage abgelehnt, z.B. wegen zu vieler Verbindungen
+    Rejected,
+    /// Die Verbindung zum Daemon ist fehlgeschlagen
+    ConnectionFailed,
+    NotRoot,
+    /// Ein Argument war ungültig
+    InvalidArgument,
+    InvalidConfig,
+    NotFound,
+    /// Lesen oder Schreiben einer Datei ist fehlgeschlagen
+    Io,
+    UpdateFailed,
+    InstallFailed,
+    ArchiveFailed,
+    SearchFailed,
+    ExportFailed,
+}
+
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Gibt mit `--json` das Ergebnis eines Befehls als `{"ok": true, "result": ...}` aus, ohne
+/// `--json` passiert nichts
+pub fn done<T: Serialize>(result: T) {
+    if json() {
+        println!("{}", json!({ "ok": true, "result": result }));
+    }
+}
+
+/// Gibt mit `--json` eine Zeile einer fortlaufenden Ausgabe (z.B. von `listen`) als JSON-Objekt aus
+pub fn line<T: Serialize>(line: T) {
+    match serde_json::to_string(&line) {
+        Ok(line) => println!("{}", line),
+        Err(err) => fail(ErrorCode::Io, &err.to_string()),
+    }
+}
+
+/// Printet einen Fehler und exitet mit 1. Mit `--json` wird stattdessen
+/// `{"ok": false, "error": {"code": ..., "message": ...}}` ausgegeben.
+pub fn fail(code: ErrorCode, message: &str) -> ! {
+    if json() {
+        println!(
+            "{}",
+            json!({ "ok": false, "error": { "code": code, "message": message } })
+        );
+    } else {
+        eprintln!("{}", message);
+    }
+    exit(1);
+}
+
+/// Wie `feddit_archivieren_assert`, nur mit Fehlercode für `--json`
+pub fn ensure(condition: bool, code: ErrorCode, message: &str) {
+    if !condition {
+        fail(code, message);
+    }
+}
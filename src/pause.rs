@@ -0,0 +1,169 @@
+#![allow(dead_code)]
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    fs::{read_to_string, File},
+    io::{ErrorKind, Write},
+    str::FromStr,
+};
+
+use crate::config::paths;
+
+/// Worauf sich eine Pause bezieht
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub enum Scope {
+    All,
+    Instance(String),
+    Community(String),
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::All => write!(f, "*"),
+            Scope::Instance(instance) => write!(f, "instance={}", instance),
+            Scope::Community(community) => write!(f, "community={}", community),
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            None if s == "*" => Ok(Scope::All),
+            Some(("instance", instance)) if !instance.is_empty() => {
+                Ok(Scope::Instance(instance.to_string()))
+            }
+            Some(("community", community)) if !community.is_empty() => {
+                Ok(Scope::Community(community.to_string()))
+            }
+            _ => Err(format!("Ungültiger Bereich: \"{}\"", s)),
+        }
+    }
+}
+
+/// Alles was gerade pausiert ist. Wird im Datenverzeichnis gespeichert, damit es einen Neustart übersteht.
+#[derive(Default)]
+pub struct Pauses {
+    scopes: BTreeSet<Scope>,
+}
+
+impl Pauses {
+    /// Liest die Pausen aus der Pausendatei, existiert die Datei nicht ist nichts pausiert
+    pub fn load() -> Result<Self, String> {
+        let content = match read_to_string(paths().pause_file()) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Pauses::default()),
+            Err(err) => {
+                return Err(format!(
+                    "Fehler beim Lesen von {}: {}",
+                    paths().pause_file(),
+                    err
+                ))
+            }
+        };
+
+        let scopes = content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.trim().parse())
+            .collect::<Result<_, _>>()?;
+
+        Ok(Pauses { scopes })
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let mut file = File::create(paths().pause_file()).map_err(|err| {
+            format!(
+                "Fehler beim Schreiben von {}: {}",
+                paths().pause_file(),
+                err
+            )
+        })?;
+        for scope in &self.scopes {
+            writeln!(file, "{}", scope).map_err(|err| {
+                format!(
+                    "Fehler beim Schreiben von {}: {}",
+                    paths().pause_file(),
+                    err
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn pause(&mut self, scope: Scope) -> Result<(), String> {
+        self.scopes.insert(scope);
+        self.save()
+    }
+
+    /// Hebt die Pause für `scope` auf, ohne Bereich werden alle Pausen aufgehoben
+    pub fn resume(&mut self, scope: Option<Scope>) -> Result<(), String> {
+        match scope {
+            Some(scope) => {
+                if !self.scopes.remove(&scope) {
+                    return Err(format!("{} ist nicht pausiert.", scope));
+                }
+            }
+            None => self.scopes.clear(),
+        }
+        self.save()
+    }
+
+    /// Returnt ob alles pausiert ist
+    pub fn all(&self) -> bool {
+        self.scopes.contains(&Scope::All)
+    }
+
+    /// Returnt ob Posts von `instance` bzw. aus `community` gerade nicht verarbeitet werden sollen
+    pub fn is_paused(&self, instance: &str, community: Option<&str>) -> bool {
+        self.all()
+            || self.scopes.contains(&Scope::Instance(instance.to_string()))
+            || community.is_some_and(|community| {
+                self.scopes
+                    .contains(&Scope::Community(community.to_string()))
+            })
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        self.scopes.iter().map(Scope::to_string).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pauses(scopes: &[&str]) -> Pauses {
+        Pauses {
+            scopes: scopes.iter().map(|scope| scope.parse().unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn scope_round_trip() {
+        for scope in ["*", "instance=feddit.de", "community=dach@feddit.de"] {
+            assert_eq!(scope.parse::<Scope>().unwrap().to_string(), scope);
+        }
+        assert!("instance=".parse::<Scope>().is_err());
+        assert!("user=x".parse::<Scope>().is_err());
+    }
+
+    #[test]
+    fn community_pause_needs_community() {
+        let pauses = pauses(&["community=dach"]);
+        assert!(!pauses.is_paused("feddit.de", None));
+        assert!(!pauses.is_paused("feddit.de", Some("politik")));
+        assert!(pauses.is_paused("feddit.de", Some("dach")));
+    }
+
+    #[test]
+    fn instance_and_global_pause() {
+        assert!(pauses(&["instance=feddit.de"]).is_paused("feddit.de", Some("dach")));
+        assert!(!pauses(&["instance=feddit.de"]).is_paused("lemmy.ml", None));
+        assert!(pauses(&["*"]).is_paused("lemmy.ml", None));
+    }
+}
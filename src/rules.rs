@@ -0,0 +1,227 @@
+#![allow(dead_code)]
+
+use chrono::Utc;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::archive::Post;
+
+/// Welche Posts archiviert werden dürfen, steht in `crawl.rules`. Ohne Angaben wird alles
+/// archiviert.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RulesConfig {
+    /// Nur diese Communities, leer heißt alle. Communities anderer Instanzen mit `@instanz`, also
+    /// so wie sie im Archiv stehen.
+    pub allow_communities: Vec<String>,
+    pub deny_communities: Vec<String>,
+    /// Posts deren Titel oder Text eines dieser Wörter enthält (Groß- und Kleinschreibung wird
+    /// ignoriert)
+    pub deny_keywords: Vec<String>,
+    /// Reguläre Ausdrücke die auf Titel und Text angewendet werden
+    pub deny_patterns: Vec<String>,
+    pub exclude_nsfw: bool,
+    /// Posts die älter sind werden übersprungen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_score: Option<i64>,
+}
+
+/// Die Regeln mit bereits kompilierten Ausdrücken
+pub struct Rules {
+    config: RulesConfig,
+    patterns: Vec<Regex>,
+}
+
+impl Rules {
+    pub fn new(config: &RulesConfig) -> Result<Rules, String> {
+        let patterns = config
+            .deny_patterns
+            .iter()
+            .map(|pattern| {
+                RegexBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|err| format!("Ungültiger Ausdruck \"{}\": {}", pattern, err))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Rules {
+            config: config.clone(),
+            patterns,
+        })
+    }
+
+    /// Überprüft einen Post, `Err` enthält den Grund warum er übersprungen wird
+    pub fn check(&self, post: &Post) -> Result<(), String> {
+        let config = &self.config;
+
+        if !config.allow_communities.is_empty()
+            && !config.allow_communities.contains(&post.community)
+        {
+            return Err(format!(
+                "Die Community {} ist nicht erlaubt.",
+                post.community
+            ));
+        }
+        if config.deny_communities.contains(&post.community) {
+            return Err(format!(
+                "Die Community {} ist ausgeschlossen.",
+                post.community
+            ));
+        }
+
+        if config.exclude_nsfw && post.nsfw {
+            return Err("NSFW-Posts sind ausgeschlossen.".to_string());
+        }
+
+        if let Some(max_age_days) = config.max_age_days {
+            let age = (Utc::now() - post.published).num_days();
+            if age > max_age_days as i64 {
+                return Err(format!(
+                    "Der Post ist {} Tage alt, erlaubt sind {}.",
+                    age, max_age_days
+                ));
+            }
+        }
+
+        if let Some(min_score) = config.min_score {
+            if post.score < min_score {
+                return Err(format!(
+                    "Der Score {} ist kleiner als {}.",
+                    post.score, min_score
+                ));
+            }
+        }
+
+        let text = format!(
+            "{}\n{}",
+            post.title,
+            post.body.as_deref().unwrap_or_default()
+        );
+        let lowercase = text.to_lowercase();
+        if let Some(keyword) = config
+            .deny_keywords
+            .iter()
+            .find(|keyword| lowercase.contains(&keyword.to_lowercase()))
+        {
+            return Err(format!("Enthält das Wort \"{}\".", keyword));
+        }
+        if let Some(pattern) = self.patterns.iter().find(|pattern| pattern.is_match(&text)) {
+            return Err(format!("Passt auf den Ausdruck \"{}\".", pattern));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn post() -> Post {
+        Post {
+            id: 1,
+            community: "politik".to_string(),
+            author: "jemand".to_string(),
+            title: "Ein Titel".to_string(),
+            body: Some("Ein Text über Bahnstreiks".to_string()),
+            url: None,
+            score: 10,
+            published: Utc::now() - Duration::days(3),
+            nsfw: false,
+            deleted: false,
+            removed: false,
+        }
+    }
+
+    fn check(config: RulesConfig, post: &Post) -> Result<(), String> {
+        Rules::new(&config).unwrap().check(post)
+    }
+
+    #[test]
+    fn no_rules_admit_everything() {
+        assert!(check(RulesConfig::default(), &post()).is_ok());
+    }
+
+    #[test]
+    fn communities() {
+        let allow = |communities: &[&str]| RulesConfig {
+            allow_communities: communities.iter().map(|c| c.to_string()).collect(),
+            ..Default::default()
+        };
+        assert!(check(allow(&["politik"]), &post()).is_ok());
+        assert!(check(allow(&["kochen"]), &post()).is_err());
+
+        let deny = RulesConfig {
+            deny_communities: vec!["politik".to_string()],
+            ..Default::default()
+        };
+        assert!(check(deny, &post()).is_err());
+    }
+
+    #[test]
+    fn keywords_and_patterns_ignore_case() {
+        let keywords = RulesConfig {
+            deny_keywords: vec!["BAHNSTREIK".to_string()],
+            ..Default::default()
+        };
+        assert!(check(keywords, &post()).is_err());
+
+        let patterns = RulesConfig {
+            deny_patterns: vec![r"^ein\s+titel$".to_string()],
+            ..Default::default()
+        };
+        // Titel und Text stehen in eigenen Zeilen, `$` passt aber nur ganz am Ende
+        assert!(check(patterns, &post()).is_ok());
+
+        let patterns = RulesConfig {
+            deny_patterns: vec![r"ein\s+TITEL".to_string()],
+            ..Default::default()
+        };
+        assert!(check(patterns, &post()).is_err());
+    }
+
+    #[test]
+    fn nsfw_age_and_score() {
+        let nsfw = RulesConfig {
+            exclude_nsfw: true,
+            ..Default::default()
+        };
+        assert!(check(nsfw.clone(), &post()).is_ok());
+        assert!(check(
+            nsfw,
+            &Post {
+                nsfw: true,
+                ..post()
+            }
+        )
+        .is_err());
+
+        let age = |days| RulesConfig {
+            max_age_days: Some(days),
+            ..Default::default()
+        };
+        assert!(check(age(7), &post()).is_ok());
+        assert!(check(age(2), &post()).is_err());
+
+        let score = |min| RulesConfig {
+            min_score: Some(min),
+            ..Default::default()
+        };
+        assert!(check(score(10), &post()).is_ok());
+        assert!(check(score(11), &post()).is_err());
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let config = RulesConfig {
+            deny_patterns: vec!["(".to_string()],
+            ..Default::default()
+        };
+        assert!(Rules::new(&config).is_err());
+    }
+}
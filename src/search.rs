@@ -0,0 +1,225 @@
+#![allow(dead_code)]
+
+use std::{cmp::Reverse, ops::Range};
+
+use chrono::{DateTime, Local, Utc};
+use clap::ValueEnum;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::archive::{self, Revision};
+
+/// Eine Suche über das Archiv. Wird als JSON an den Daemon geschickt, wenn er läuft.
+#[derive(Serialize, Deserialize)]
+pub struct SearchQuery {
+    pub text: String,
+    pub community: Option<String>,
+    pub author: Option<String>,
+    pub instance: Option<String>,
+    pub since: Option<DateTime<Local>>,
+    pub until: Option<DateTime<Local>>,
+    pub min_score: Option<i64>,
+    pub sort: Sort,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, ValueEnum)]
+pub enum Sort {
+    /// Neuste zuerst
+    New,
+    /// Älteste zuerst
+    Old,
+    /// Höchster Score zuerst
+    Score,
+}
+
+/// Ein Post oder Kommentar auf den die Suche zutrifft
+#[derive(Serialize, Deserialize)]
+pub struct SearchHit {
+    pub instance: String,
+    pub post_id: i32,
+    /// Gesetzt wenn der Treffer ein Kommentar ist
+    pub comment_id: Option<i32>,
+    pub community: String,
+    pub author: String,
+    pub title: String,
+    /// Der Text um den Treffer herum
+    pub excerpt: String,
+    pub score: i64,
+    pub published: DateTime<Utc>,
+}
+
+/// Durchsucht die Titel, Texte und Kommentare der neusten Revision jedes archivierten Threads.
+/// Threads die nicht gelesen werden können werden übersprungen und an `skipped` gegeben.
+pub fn search(
+    query: &SearchQuery,
+    mut skipped: impl FnMut(String),
+) -> Result<Vec<SearchHit>, String> {
+    let text = RegexBuilder::new(&regex::escape(&query.text))
+        .case_insensitive(true)
+        .build()
+        .map_err(|err| format!("Ungültiger Suchtext: {}", err))?;
+    let mut hits = Vec::new();
+
+    for thread in archive::threads()? {
+        let thread = match thread {
+            Ok(thread) => thread,
+            Err(err) => {
+                skipped(err);
+                continue;
+            }
+        };
+        let Some(revision) = thread.latest() else {
+            skipped(format!(
+                "{}/{} hat keine Revision.",
+                thread.instance, thread.id
+            ));
+            continue;
+        };
+        if !query.matches_thread(&thread.instance, revision) {
+            continue;
+        }
+
+        let post = &revision.post;
+        let hit = |comment_id, author: &str, excerpt, score, published| SearchHit {
+            instance: thread.instance.clone(),
+            post_id: post.id,
+            comment_id,
+            community: post.community.clone(),
+            author: author.to_string(),
+            title: post.title.clone(),
+            excerpt,
+            score,
+            published,
+        };
+
+        if query.matches_item(&post.author, post.score, post.published) {
+            let body = post.body.as_deref().unwrap_or_default();
+            if find(&post.title, &text).is_some() {
+                hits.push(hit(
+                    None,
+                    &post.author,
+                    excerpt(body, 0..0),
+                    post.score,
+                    post.published,
+                ));
+            } else if let Some(found) = find(body, &text) {
+                hits.push(hit(
+                    None,
+                    &post.author,
+                    excerpt(body, found),
+                    post.score,
+                    post.published,
+                ));
+            }
+        }
+
+        for comment in &revision.comments {
+            if !query.matches_item(&comment.author, comment.score, comment.published) {
+                continue;
+            }
+            if let Some(found) = find(&comment.body, &text) {
+                hits.push(hit(
+                    Some(comment.id),
+                    &comment.author,
+                    excerpt(&comment.body, found),
+                    comment.score,
+                    comment.published,
+                ));
+            }
+        }
+    }
+
+    match query.sort {
+        Sort::New => hits.sort_by_key(|hit| Reverse(hit.published)),
+        Sort::Old => hits.sort_by_key(|hit| hit.published),
+        Sort::Score => hits.sort_by_key(|hit| Reverse(hit.score)),
+    }
+    if let Some(limit) = query.limit {
+        hits.truncate(limit);
+    }
+
+    Ok(hits)
+}
+
+impl SearchQuery {
+    fn matches_thread(&self, instance: &str, revision: &Revision) -> bool {
+        self.instance.as_ref().map_or(true, |i| i == instance)
+            && self
+                .community
+                .as_ref()
+                .map_or(true, |community| *community == revision.post.community)
+    }
+
+    fn matches_item(&self, author: &str, score: i64, published: DateTime<Utc>) -> bool {
+        self.author.as_ref().map_or(true, |a| a == author)
+            && self.min_score.map_or(true, |min| score >= min)
+            && self.since.map_or(true, |since| published >= since)
+            && self.until.map_or(true, |until| published <= until)
+    }
+}
+
+/// Sucht `needle` in `text`. Returnt die Stelle des Treffers in Zeichen des originalen Texts.
+fn find(text: &str, needle: &Regex) -> Option<Range<usize>> {
+    let found = needle.find(text)?;
+    let start = text[..found.start()].chars().count();
+    Some(start..start + found.as_str().chars().count())
+}
+
+/// Returnt den Text um die Stelle `found` (in Zeichen) herum, in eine Zeile gepackt
+fn excerpt(text: &str, found: Range<usize>) -> String {
+    const CONTEXT: usize = 40;
+
+    let chars: Vec<char> = text.chars().collect();
+    let start = found.start.saturating_sub(CONTEXT).min(chars.len());
+    let end = (found.end + CONTEXT).min(chars.len());
+
+    let mut excerpt: String = chars[start..end.max(start)].iter().collect();
+    excerpt = excerpt.split_whitespace().collect::<Vec<_>>().join(" ");
+    if start > 0 {
+        excerpt.insert(0, '…');
+    }
+    if end < chars.len() {
+        excerpt.push('…');
+    }
+    excerpt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn needle(text: &str) -> Regex {
+        RegexBuilder::new(&regex::escape(text))
+            .case_insensitive(true)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn find_ignores_case() {
+        assert_eq!(find("Hallo Welt", &needle("WELT")), Some(6..10));
+        assert_eq!(find("Hallo Welt", &needle("mond")), None);
+    }
+
+    #[test]
+    fn find_counts_chars_of_original_text() {
+        // "ẞ" wird kleingeschrieben zu "ß", die Positionen müssen trotzdem zum Original passen
+        assert_eq!(find("Größe ẞ Straße", &needle("straße")), Some(8..14));
+        assert_eq!(find("İstanbul", &needle("stan")), Some(1..5));
+    }
+
+    #[test]
+    fn find_escapes_needle() {
+        assert_eq!(find("a.b (c)", &needle("(c)")), Some(4..7));
+        assert_eq!(find("axb", &needle("a.b")), None);
+    }
+
+    #[test]
+    fn excerpt_around_hit() {
+        let text = format!("{}Treffer{}", "a ".repeat(30), " b".repeat(30));
+        let excerpt = excerpt(&text, find(&text, &needle("treffer")).unwrap());
+        assert!(excerpt.starts_with('…') && excerpt.ends_with('…'));
+        assert!(excerpt.contains("Treffer"));
+    }
+}
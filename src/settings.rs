@@ -1,21 +1,42 @@
 #![allow(dead_code)]
 
-pub const GIT_BRANCH: &'static str = "main";
-pub const DAEMON_PATH: &'static str = "/usr/bin/feddit_archive_daemon";
-pub const CLIENT_PATH: &'static str = "/usr/bin/feddit_archivieren";
-pub const RUN_DIR: &'static str = "/run/feddit_archivieren";
-pub const PID_FILE: &'static str = "/run/feddit_archivieren/daemon.pid";
-pub const ERR_FILE: &'static str = "/run/feddit_archivieren/daemon.err";
-pub const OUT_FILE: &'static str = "/run/feddit_archivieren/daemon.out";
-pub const URL_FILE: &'static str = "/run/feddit_archivieren/url.txt";
-pub const UPDATE_LOG_FILE: &'static str = "/run/feddit_archivieren/update_log.txt";
-pub const POST_FILE: &'static str = "/run/feddit_archivieren/posts.txt";
-pub const SOCKET_FILE: &'static str = "/run/feddit_archivieren/daemon.sck";
-pub const UDPATE_DIR: &'static str = "/var/tmp/feddit_archivieren";
-pub const UDPATE_CACHE_DIR: &'static str = "/var/tmp/feddit_archivieren_cache";
-pub const GITHUB_LINK: &'static str = "https://github.com/Einfachirgendwa1/feddit_archivieren";
-pub const FEDDIT_LINK: &'static str =
-    "https://feddit.de/?dataType=Post&listingType=Local&page=1&sort=New";
+pub const DAEMON_PATH: &str = "/usr/bin/feddit_archive_daemon";
+pub const CLIENT_PATH: &str = "/usr/bin/feddit_archivieren";
+pub const RUN_DIR: &str = "/run/feddit_archivieren";
+/// Wie viele alte Versionen der Logs des Daemons behalten werden
+pub const LOG_ROTATIONS: usize = 5;
+pub const CONFIG_FILE: &str = "/etc/feddit_archivieren/config.toml";
+/// Ist diese Umgebungsvariable "1", laufen Client und Daemon im Benutzermodus ohne root
+pub const USER_MODE_VAR: &str = "FEDDIT_ARCHIVIEREN_USER";
+/// Der Name des Profils, wenn nicht das Standardprofil verwendet wird
+pub const PROFILE_VAR: &str = "FEDDIT_ARCHIVIEREN_PROFILE";
+/// Mit diesem Präfix überschreiben Umgebungsvariablen die Konfiguration, z.B.
+/// `FEDDIT_ARCHIVIEREN_UPDATE_INTERVAL` für `update.interval`
+pub const ENV_PREFIX: &str = "FEDDIT_ARCHIVIEREN_";
+/// Die Überschreibungen von `--set`, eine `key=value` Zeile pro Wert
+pub const OVERRIDES_VAR: &str = "FEDDIT_ARCHIVIEREN_SET";
+pub const DATA_DIR: &str = "/var/lib/feddit_archivieren";
+pub const UDPATE_DIR: &str = "/var/tmp/feddit_archivieren";
+pub const UDPATE_CACHE_DIR: &str = "/var/tmp/feddit_archivieren_cache";
+/// Der Updatekanal einer neuen Installation
+pub const UPDATE_CHANNEL: &str = "stable";
+pub const GITHUB_LINK: &str = "https://github.com/Einfachirgendwa1/feddit_archivieren";
+pub const FEDDIT_LINK: &str = "https://feddit.de/?dataType=Post&listingType=Local&page=1&sort=New";
 
 pub const TCP_BUFFER_SIZE: usize = 1024;
+/// Wie viele Anfragen höchstens pro Minute an eine Instanz gehen, Lemmy erlaubt standardmäßig 180
+pub const REQUESTS_PER_MINUTE: u32 = 60;
+/// So viele Bytes darf ein heruntergeladenes Bild oder Video höchstens haben
+pub const MAX_MEDIA_SIZE: u64 = 50 * 1024 * 1024;
+/// So lange wartet der Crawler mindestens zwischen zwei Runden über alle Ziele
+pub const CRAWL_DELAY: std::time::Duration = std::time::Duration::from_secs(10);
 pub const UPDATE_FETCH_DELAY: std::time::Duration = std::time::Duration::from_secs(120);
+/// Wie viele vorher installierte Versionen für `rollback` aufgehoben werden
+pub const KEEP_VERSIONS: usize = 3;
+/// So lange muss der Daemon nach einem Update gesund bleiben, sonst wird zurückgerollt
+pub const HEALTH_GRACE: std::time::Duration = std::time::Duration::from_secs(30);
+pub const BACKLOG_SIZE: usize = 1000;
+pub const CONNECTION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+pub const SESSION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(600);
+pub const MAX_CONNECTIONS: usize = 32;
+pub const MAX_LISTENERS: usize = 16;
@@ -0,0 +1,200 @@
+use chrono::Local;
+use colored::{Color, Colorize};
+
+use crate::archive::{self, removal_label, Revision, Thread};
+
+/// Die Farben der Kommentarebenen, damit man im Baum sieht was zusammengehört
+const DEPTH_COLORS: &[Color] = &[
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::Green,
+    Color::Yellow,
+];
+
+/// Sucht einen Thread anhand von `<instanz>/<post id>` oder nur `<post id>`. Ohne Instanz wird in
+/// allen Instanzen gesucht, kommt die ID mehrmals vor muss die Instanz angegeben werden.
+pub fn find_thread(post: &str) -> Result<Thread, String> {
+    let (instance, id) = match post.rsplit_once('/') {
+        Some((instance, id)) => (Some(instance.to_string()), id),
+        None => (None, post),
+    };
+    let id: i32 = id
+        .parse()
+        .map_err(|_| format!("Ungültige Post ID: \"{}\"", id))?;
+
+    // Die Instanz wird ein Verzeichnis im Archiv, sie darf nicht aus ihm herausführen
+    if let Some(instance) = &instance {
+        if instance.is_empty() || instance.contains(['/', '\\']) || instance.contains("..") {
+            return Err(format!("Ungültige Instanz: \"{}\"", instance));
+        }
+    }
+
+    let instances = match instance {
+        Some(instance) => vec![instance],
+        None => archive::instances()?,
+    };
+
+    let mut found = Vec::new();
+    for instance in instances {
+        if let Some(thread) = Thread::load(&instance, id)? {
+            found.push(thread);
+        }
+    }
+
+    match found.len() {
+        0 => Err(format!("Post {} ist nicht archiviert.", post)),
+        1 => Ok(found.remove(0)),
+        _ => Err(format!(
+            "Post {} gibt es auf mehreren Instanzen, bitte eine angeben: {}",
+            id,
+            found
+                .iter()
+                .map(|thread| format!("{}/{}", thread.instance, id))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Printet einen Thread mit Kommentarbaum. `revision` zählt ab 1, ohne Angabe wird die neuste
+/// Revision gezeigt.
+pub fn show(thread: &Thread, revision: Option<usize>) -> Result<(), String> {
+    let count = thread.revisions.len();
+    let number = revision.unwrap_or(count);
+    if number == 0 || number > count {
+        return Err(format!(
+            "Revision {} existiert nicht, es gibt die Revisionen 1 bis {}.",
+            number, count
+        ));
+    }
+    let revision = &thread.revisions[number - 1];
+
+    print_post(thread, revision);
+    println!(
+        "{}",
+        format!(
+            "Revision {} von {}, archiviert am {}",
+            number,
+            count,
+            revision.archived.format("%d.%m.%Y %H:%M")
+        )
+        .dimmed()
+    );
+    println!();
+
+    if revision.comments.is_empty() {
+        println!("{}", "Keine Kommentare.".dimmed());
+    } else {
+        println!(
+            "{}",
+            format!("{} Kommentare", revision.comments.len()).bold()
+        );
+        print_comments(revision, None, 0);
+    }
+
+    Ok(())
+}
+
+fn print_post(thread: &Thread, revision: &Revision) {
+    let post = &revision.post;
+
+    let mut title = post.title.bold().to_string();
+    if let Some(label) = removal_label(post.deleted, post.removed) {
+        title = format!("{} {}", format!("[{}]", label).red().bold(), title);
+    }
+    if post.nsfw {
+        title = format!("{} {}", "[NSFW]".red(), title);
+    }
+    println!("{}", title);
+
+    println!(
+        "{} von {} auf {} | Score {} | {}",
+        post.community.yellow(),
+        post.author.green(),
+        thread.instance,
+        score(post.score),
+        post.published
+            .with_timezone(&Local)
+            .format("%d.%m.%Y %H:%M")
+    );
+
+    if let Some(url) = &post.url {
+        println!("{}", url.underline());
+    }
+    if let Some(body) = &post.body {
+        println!();
+        if post.deleted || post.removed {
+            println!("{}", body.dimmed());
+        } else {
+            println!("{}", body);
+        }
+    }
+    println!();
+}
+
+/// Printet alle Antworten auf `parent` eingerückt unter ihm
+fn print_comments(revision: &Revision, parent: Option<i32>, depth: usize) {
+    for comment in revision.replies(parent) {
+        let color = DEPTH_COLORS[depth % DEPTH_COLORS.len()];
+        let indent: String = (0..=depth)
+            .map(|level| {
+                "│ "
+                    .color(DEPTH_COLORS[level % DEPTH_COLORS.len()])
+                    .to_string()
+            })
+            .collect();
+
+        let mut header = format!(
+            "{} | {} | {}",
+            comment.author.color(color).bold(),
+            score(comment.score),
+            comment
+                .published
+                .with_timezone(&Local)
+                .format("%d.%m.%Y %H:%M")
+        );
+        if let Some(label) = removal_label(comment.deleted, comment.removed) {
+            header = format!("{} {}", format!("[{}]", label).red().bold(), header);
+        }
+
+        println!("{}", indent);
+        println!("{}{}", indent, header);
+        for line in comment.body.lines() {
+            if comment.deleted || comment.removed {
+                println!("{}{}", indent, line.dimmed());
+            } else {
+                println!("{}{}", indent, line);
+            }
+        }
+
+        print_comments(revision, Some(comment.id), depth + 1);
+    }
+}
+
+fn score(score: i64) -> String {
+    match score {
+        score if score > 0 => format!("+{}", score).green().to_string(),
+        score if score < 0 => score.to_string().red().to_string(),
+        score => score.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_thread_rejects_paths_as_instance() {
+        for post in [
+            "../../etc/5",
+            "../5",
+            "/5",
+            "feddit.de\\..\\x/5",
+            "../feddit.de/5",
+        ] {
+            let err = find_thread(post).err().unwrap();
+            assert!(err.starts_with("Ungültige Instanz"), "{}: {}", post, err);
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{read_dir, symlink_metadata},
+    path::Path,
+};
+
+use chrono::Datelike;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{archive, config::paths, status::Status};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Period {
+    Day,
+    Week,
+}
+
+#[derive(Serialize)]
+pub struct Stats {
+    pub posts: usize,
+    pub comments: usize,
+    pub users: usize,
+    pub deleted: usize,
+    pub removed: usize,
+    pub media_bytes: u64,
+    pub instances: Vec<InstanceStats>,
+    /// Wie viele Posts und Kommentare pro Tag bzw. Woche dazugekommen sind, gemessen an der ersten
+    /// Archivierung des Posts
+    pub growth: Vec<Growth>,
+    /// Nur vorhanden wenn der Daemon läuft
+    pub crawl: Option<CrawlStats>,
+}
+
+#[derive(Serialize)]
+pub struct InstanceStats {
+    pub instance: String,
+    pub posts: usize,
+    pub comments: usize,
+    pub users: usize,
+    pub communities: Vec<CommunityStats>,
+}
+
+#[derive(Serialize)]
+pub struct CommunityStats {
+    pub community: String,
+    pub posts: usize,
+    pub comments: usize,
+    pub users: usize,
+}
+
+#[derive(Serialize)]
+pub struct Growth {
+    pub period: String,
+    pub posts: usize,
+    pub comments: usize,
+}
+
+#[derive(Serialize)]
+pub struct CrawlStats {
+    pub archived: usize,
+    pub archive_errors: usize,
+    pub crawl_errors: usize,
+    /// Anteil der fehlgeschlagenen Versuche an allen Versuchen, zwischen 0 und 1
+    pub error_rate: f64,
+}
+
+/// Zählt Posts, Kommentare und Nutzer
+#[derive(Default)]
+struct Counter {
+    posts: usize,
+    comments: usize,
+    users: BTreeSet<String>,
+}
+
+/// Geht einmal durch das ganze Archiv und sammelt die Statistiken. `status` ist der Status des
+/// laufenden Daemons, falls es einen gibt. Threads die nicht gelesen werden können oder keine
+/// Revision haben werden übersprungen und an `skipped` gegeben.
+pub fn collect(
+    period: Period,
+    status: Option<&Status>,
+    mut skipped: impl FnMut(String),
+) -> Result<Stats, String> {
+    let mut total = Counter::default();
+    let mut instances: BTreeMap<String, (Counter, BTreeMap<String, Counter>)> = BTreeMap::new();
+    let mut growth: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    let mut deleted = 0;
+    let mut removed = 0;
+
+    for thread in archive::threads()? {
+        let thread = match thread {
+            Ok(thread) => thread,
+            Err(err) => {
+                skipped(err);
+                continue;
+            }
+        };
+        let (Some(first), Some(revision)) = (thread.revisions.first(), thread.latest()) else {
+            skipped(format!(
+                "{}/{} hat keine Revision.",
+                thread.instance, thread.id
+            ));
+            continue;
+        };
+        let first_archived = first.archived;
+        let post = &revision.post;
+
+        let (instance, communities) = instances.entry(thread.instance.clone()).or_default();
+        let community = communities.entry(post.community.clone()).or_default();
+
+        for counter in [&mut total, &mut *instance, &mut *community] {
+            counter.posts += 1;
+            counter.comments += revision.comments.len();
+            counter.users.insert(post.author.clone());
+            for comment in &revision.comments {
+                counter.users.insert(comment.author.clone());
+            }
+        }
+
+        deleted += post.deleted as usize;
+        removed += post.removed as usize;
+        for comment in &revision.comments {
+            deleted += comment.deleted as usize;
+            removed += comment.removed as usize;
+        }
+
+        let period = match period {
+            Period::Day => first_archived.format("%Y-%m-%d").to_string(),
+            Period::Week => {
+                let week = first_archived.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+        };
+        let entry = growth.entry(period).or_default();
+        entry.0 += 1;
+        entry.1 += revision.comments.len();
+    }
+
+    let crawl = status.map(|status| {
+        let crawl_errors: usize = status.targets.iter().map(|target| target.errors).sum();
+        let attempts = status.archived + status.archive_errors + crawl_errors;
+        CrawlStats {
+            archived: status.archived,
+            archive_errors: status.archive_errors,
+            crawl_errors,
+            error_rate: match attempts {
+                0 => 0.0,
+                attempts => (status.archive_errors + crawl_errors) as f64 / attempts as f64,
+            },
+        }
+    });
+
+    Ok(Stats {
+        posts: total.posts,
+        comments: total.comments,
+        users: total.users.len(),
+        deleted,
+        removed,
+        media_bytes: dir_size(Path::new(&paths().media_dir())),
+        instances: instances
+            .into_iter()
+            .map(|(name, (instance, communities))| InstanceStats {
+                instance: name,
+                posts: instance.posts,
+                comments: instance.comments,
+                users: instance.users.len(),
+                communities: communities
+                    .into_iter()
+                    .map(|(name, community)| CommunityStats {
+                        community: name,
+                        posts: community.posts,
+                        comments: community.comments,
+                        users: community.users.len(),
+                    })
+                    .collect(),
+            })
+            .collect(),
+        growth: growth
+            .into_iter()
+            .map(|(period, (posts, comments))| Growth {
+                period,
+                posts,
+                comments,
+            })
+            .collect(),
+        crawl,
+    })
+}
+
+/// Die Größe aller Dateien in einem Verzeichnis, inklusive Unterverzeichnissen
+fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = read_dir(dir) else {
+        return 0;
+    };
+
+    entries
+        .map_while(Result::ok)
+        .map(|entry| match symlink_metadata(entry.path()) {
+            Ok(metadata) if metadata.is_dir() => dir_size(&entry.path()),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Formatiert eine Anzahl an Bytes, z.B. `12.3 MiB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Printet die Statistiken lesbar
+pub fn print_stats(stats: &Stats) {
+    println!(
+        "Archiviert:\t{} Posts, {} Kommentare, {} Nutzer",
+        stats.posts, stats.comments, stats.users
+    );
+    println!("Gelöscht:\t{}", stats.deleted);
+    println!("Entfernt:\t{}", stats.removed);
+    println!("Medien:\t\t{}", format_bytes(stats.media_bytes));
+
+    match &stats.crawl {
+        Some(crawl) => println!(
+            "Fehlerrate:\t{:.1}% ({} Crawl-Fehler, {} Archiv-Fehler, {} archiviert)",
+            crawl.error_rate * 100.0,
+            crawl.crawl_errors,
+            crawl.archive_errors,
+            crawl.archived
+        ),
+        None => println!("Fehlerrate:\tunbekannt (der Daemon läuft nicht)"),
+    }
+
+    for instance in &stats.instances {
+        println!();
+        println!(
+            "{}: {} Posts, {} Kommentare, {} Nutzer",
+            instance.instance, instance.posts, instance.comments, instance.users
+        );
+        for community in &instance.communities {
+            println!(
+                "\t{}: {} Posts, {} Kommentare, {} Nutzer",
+                community.community, community.posts, community.comments, community.users
+            );
+        }
+    }
+
+    if !stats.growth.is_empty() {
+        println!();
+        println!("Wachstum:");
+        for growth in &stats.growth {
+            println!(
+                "\t{}: +{} Posts, +{} Kommentare",
+                growth.period, growth.posts, growth.comments
+            );
+        }
+    }
+}
@@ -0,0 +1,77 @@
+#![allow(dead_code)]
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+/// Der Zustand des laufenden Daemons, so wie er auf `status` antwortet
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Status {
+    pub version: String,
+    pub started: DateTime<Local>,
+    /// Wie lange der Daemon schon läuft, in Sekunden. Wird beim Beantworten von `status` gesetzt.
+    pub uptime: i64,
+    pub targets: Vec<TargetStatus>,
+    /// Anzahl der Posts die der Feddit-Thread gefunden hat, die aber noch nicht archiviert wurden
+    pub queue: usize,
+    pub archived: usize,
+    pub archive_errors: usize,
+    pub last_update_check: Option<UpdateCheck>,
+    /// Alles was gerade pausiert ist, z.B. `*` oder `instance=feddit.de`
+    pub paused: Vec<String>,
+}
+
+/// Der Fortschritt des Crawlers bei einem Ziel
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TargetStatus {
+    pub url: String,
+    /// Die Seite die als nächstes gecrawlt wird
+    pub page: u32,
+    /// Der letzte Post auf der zuletzt gecrawlten Seite
+    pub last_post: Option<i32>,
+    pub last_fetch: Option<DateTime<Local>>,
+    pub errors: usize,
+}
+
+/// Das Ergebnis der letzten Updateüberprüfung
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UpdateCheck {
+    pub time: DateTime<Local>,
+    pub error: Option<String>,
+}
+
+impl Status {
+    pub fn new(targets: &[String]) -> Self {
+        Status {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            started: Local::now(),
+            uptime: 0,
+            targets: targets.iter().map(|url| TargetStatus::new(url)).collect(),
+            queue: 0,
+            archived: 0,
+            archive_errors: 0,
+            last_update_check: None,
+            paused: Vec::new(),
+        }
+    }
+}
+
+impl TargetStatus {
+    /// Erstellt den Status für ein Ziel, die Startseite wird aus dem `page` Parameter der URL gelesen
+    pub fn new(url: &str) -> Self {
+        TargetStatus {
+            url: url.to_string(),
+            page: TargetStatus::first_page(url),
+            last_post: None,
+            last_fetch: None,
+            errors: 0,
+        }
+    }
+
+    /// Die Seite bei der der Crawler anfängt, und wieder anfängt wenn er hinten angekommen ist
+    pub fn first_page(url: &str) -> u32 {
+        url.split(['?', '&'])
+            .find_map(|param| param.strip_prefix("page="))
+            .and_then(|page| page.parse().ok())
+            .unwrap_or(1)
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    cmp::Reverse,
+    fs::{copy, create_dir_all, read_dir, remove_dir_all, remove_file},
+    path::{Path, PathBuf},
+    process::Command,
+    time::SystemTime,
+};
+
+use semver::Version;
+
+use crate::config::paths;
+
+const DAEMON: &str = "daemon";
+const CLIENT: &str = "client";
+
+/// Eine vorher installierte Version, aufgehoben in `versions_dir`
+pub struct Retained {
+    pub version: Version,
+    pub dir: PathBuf,
+    /// Wann sie aufgehoben wurde, also ungefähr wann sie ersetzt wurde
+    pub time: SystemTime,
+}
+
+/// Die aufgehobenen Versionen, die zuletzt ersetzte zuerst
+pub fn list() -> Result<Vec<Retained>, String> {
+    let dir = paths().versions_dir();
+    let entries = match read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(format!("Fehler beim Lesen von {}: {}", dir, err)),
+    };
+
+    let mut versions: Vec<Retained> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let version = Version::parse(entry.file_name().to_str()?).ok()?;
+            let time = entry.path().join(DAEMON).metadata().ok()?.modified().ok()?;
+            Some(Retained {
+                version,
+                dir: entry.path(),
+                time,
+            })
+        })
+        .collect();
+    versions.sort_by_key(|retained| Reverse(retained.time));
+    Ok(versions)
+}
+
+/// Die Version der installierten binarys, falls es welche gibt
+pub fn installed() -> Option<Version> {
+    let output = Command::new(&paths().client_binary)
+        .arg("--version")
+        .output()
+        .ok()?;
+    // clap printet "<name> <version>"
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Version::parse(stdout.split_whitespace().last()?).ok()
+}
+
+/// Hebt die installierten binarys auf, bevor sie ersetzt werden. Danach bleiben nur die `keep`
+/// zuletzt aufgehobenen Versionen und `protect` übrig. Returnt die aufgehobene Version.
+pub fn retain(keep: usize, protect: Option<&Version>) -> Result<Option<Version>, String> {
+    if keep == 0 || !Path::new(&paths().daemon_binary).exists() {
+        return Ok(None);
+    }
+    let Some(version) = installed() else {
+        return Ok(None);
+    };
+
+    let dir = Path::new(&paths().versions_dir()).join(version.to_string());
+    create_dir_all(&dir).map_err(|err| format!("Fehler beim Erstellen von {:?}: {}", dir, err))?;
+    // Der Client zuerst, die Zeit des Daemons zählt als Zeitpunkt des Aufhebens
+    for (binary, name) in [
+        (&paths().client_binary, CLIENT),
+        (&paths().daemon_binary, DAEMON),
+    ] {
+        copy(binary, dir.join(name))
+            .map_err(|err| format!("Fehler beim Kopieren von {}: {}", binary, err))?;
+    }
+
+    for old in outdated(list()?, keep, protect) {
+        remove_dir_all(&old.dir)
+            .map_err(|err| format!("Fehler beim Löschen von {:?}: {}", old.dir, err))?;
+    }
+
+    Ok(Some(version))
+}
+
+/// Die Versionen aus `versions` (die zuletzt ersetzte zuerst), die nach den `keep` neuesten
+/// kommen. `protect` wird nie gelöscht und zählt auch nicht zu den `keep`.
+fn outdated(versions: Vec<Retained>, keep: usize, protect: Option<&Version>) -> Vec<Retained> {
+    versions
+        .into_iter()
+        .filter(|retained| Some(&retained.version) != protect)
+        .skip(keep)
+        .collect()
+}
+
+/// Installiert eine aufgehobene Version wieder, der Daemon darf dabei nicht laufen
+pub fn restore(retained: &Retained) -> Result<(), String> {
+    for (name, binary) in [
+        (DAEMON, &paths().daemon_binary),
+        (CLIENT, &paths().client_binary),
+    ] {
+        // Erst löschen, in eine laufende binary (z.B. diesen Client) kann nicht geschrieben werden
+        if Path::new(binary).exists() {
+            remove_file(binary)
+                .map_err(|err| format!("Fehler beim Löschen von {}: {}", binary, err))?;
+        }
+        let from = retained.dir.join(name);
+        copy(&from, binary).map_err(|err| {
+            format!(
+                "Fehler beim Kopieren von {:?} nach {}: {}",
+                from, binary, err
+            )
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Aufgehobene Versionen `0.0.<patch>`, in der Reihenfolge von `list`
+    fn retained(patches: &[u64]) -> Vec<Retained> {
+        patches
+            .iter()
+            .enumerate()
+            .map(|(age, &patch)| Retained {
+                version: Version::new(0, 0, patch),
+                dir: PathBuf::from(format!("0.0.{}", patch)),
+                time: SystemTime::UNIX_EPOCH + Duration::from_secs(1000 - age as u64),
+            })
+            .collect()
+    }
+
+    fn patches(versions: Vec<Retained>) -> Vec<u64> {
+        versions
+            .iter()
+            .map(|retained| retained.version.patch)
+            .collect()
+    }
+
+    #[test]
+    fn outdated_keeps_the_newest() {
+        assert_eq!(patches(outdated(retained(&[5, 4, 3, 2]), 2, None)), [3, 2]);
+        assert!(outdated(retained(&[5, 4]), 2, None).is_empty());
+        assert!(outdated(retained(&[]), 2, None).is_empty());
+    }
+
+    #[test]
+    fn outdated_by_replacement_not_version() {
+        // Nach einem Rollback wurde die höhere Version zuletzt ersetzt
+        assert_eq!(patches(outdated(retained(&[2, 5, 4]), 1, None)), [5, 4]);
+    }
+
+    #[test]
+    fn outdated_never_protected() {
+        let protect = Version::new(0, 0, 2);
+        assert_eq!(
+            patches(outdated(retained(&[5, 4, 3, 2]), 1, Some(&protect))),
+            [4, 3]
+        );
+        // Die geschützte Version zählt nicht zu den behaltenen
+        let protect = Version::new(0, 0, 5);
+        assert_eq!(
+            patches(outdated(retained(&[5, 4, 3]), 1, Some(&protect))),
+            [3]
+        );
+    }
+}